@@ -1,17 +1,313 @@
+#[cfg(feature = "lmdb")]
+use crate::lmdb::LmdbDB;
+#[cfg(feature = "memcached")]
+use crate::memcached::Memcached;
+use crate::memory::MemoryDB;
+#[cfg(feature = "postgres")]
+use crate::postgres::Postgres;
+use crate::properties::{DBInstance, Properties};
+#[cfg(feature = "redb")]
+use crate::redb::RedbDB;
+#[cfg(feature = "redis")]
+use crate::redis::Redis;
+#[cfg(feature = "rest")]
+use crate::rest::Rest;
+#[cfg(feature = "rocksdb")]
+use crate::rocksdb::RocksDB;
+#[cfg(feature = "sled")]
+use crate::sled::SledDB;
 use crate::sqlite::SQLite;
 use anyhow::{anyhow, Result};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Bounds the memory spent on remembering which errors were already logged.
+const MAX_LOGGED_ERRORS: usize = 1024;
+
+/// Outcome of a database operation, mirroring upstream YCSB's `Status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Status {
+    Ok,
+    Error,
+    NotFound,
+    NotImplemented,
+    UnexpectedState,
+    BadRequest,
+    Forbidden,
+    ServiceUnavailable,
+    BatchedOk,
+    // Not in upstream YCSB, reported by SQLite.
+    Busy,
+    Locked,
+    Constraint,
+}
+
+impl Status {
+    pub const ALL: [Status; 12] = [
+        Status::Ok,
+        Status::Error,
+        Status::NotFound,
+        Status::NotImplemented,
+        Status::UnexpectedState,
+        Status::BadRequest,
+        Status::Forbidden,
+        Status::ServiceUnavailable,
+        Status::BatchedOk,
+        Status::Busy,
+        Status::Locked,
+        Status::Constraint,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::Error => "ERROR",
+            Status::NotFound => "NOT_FOUND",
+            Status::NotImplemented => "NOT_IMPLEMENTED",
+            Status::UnexpectedState => "UNEXPECTED_STATE",
+            Status::BadRequest => "BAD_REQUEST",
+            Status::Forbidden => "FORBIDDEN",
+            Status::ServiceUnavailable => "SERVICE_UNAVAILABLE",
+            Status::BatchedOk => "BATCHED_OK",
+            Status::Busy => "BUSY",
+            Status::Locked => "LOCKED",
+            Status::Constraint => "CONSTRAINT",
+        }
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Maps the result of an operation to its status, counting every error as
+/// `Status::Error`.
+pub fn error_status(result: Result<Status>) -> Status {
+    error_status_with(result, |_| Status::Error)
+}
+
+/// Maps the result of an operation to its status, with `classify` choosing the
+/// status the workload counts an error under, e.g. SERVICE_UNAVAILABLE for a
+/// refused connection. An error is logged the first time its status and root
+/// cause are seen, since the context often contains the key, so that a failing
+/// run says why without flooding the output.
+pub fn error_status_with(
+    result: Result<Status>,
+    classify: impl FnOnce(&anyhow::Error) -> Status,
+) -> Status {
+    let err = match result {
+        Ok(status) => return status,
+        Err(err) => err,
+    };
+    let status = classify(&err);
+    static LOGGED: OnceLock<Mutex<HashSet<(Status, String)>>> = OnceLock::new();
+    let cause = (status, err.root_cause().to_string());
+    let mut logged = LOGGED.get_or_init(Default::default).lock().unwrap();
+    if logged.len() < MAX_LOGGED_ERRORS && logged.insert(cause) {
+        log::error!("{}: {:#}", status, err);
+    }
+    status
+}
+
+/// Whether `field` is one of `fields`, for backends that serialize every
+/// record into one value and so can only read whole records.
+pub fn field_selected(fields: Option<&[&str]>, field: &str) -> bool {
+    fields.is_none_or(|fields| fields.contains(&field))
+}
 
 pub trait DB {
     fn init(&self) -> Result<()>;
-    fn insert(&self, table: &str, key: &str, values: &HashMap<&str, String>) -> Result<()>;
-    fn read(&self, table: &str, key: &str, result: &mut HashMap<String, String>) -> Result<()>;
+    fn cleanup(&self) -> Result<()> {
+        Ok(())
+    }
+    fn insert(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status;
+    /// Inserts a record during the load phase. Nothing reads the record before
+    /// the phase ends, so a backend may defer it into a batch.
+    fn insert_batched(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        self.insert(table, key, values)
+    }
+    /// Reads `fields` of a record, or all of them if `fields` is `None`.
+    fn read(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut HashMap<String, Vec<u8>>,
+    ) -> Status;
+    fn update(&self, _table: &str, _key: &str, _values: &HashMap<&str, Vec<u8>>) -> Status {
+        Status::NotImplemented
+    }
+    fn delete(&self, _table: &str, _key: &str) -> Status {
+        Status::NotImplemented
+    }
+    fn scan(
+        &self,
+        _table: &str,
+        _start_key: &str,
+        _record_count: usize,
+        _result: &mut Vec<HashMap<String, Vec<u8>>>,
+    ) -> Status {
+        Status::NotImplemented
+    }
+}
+
+/// Creates an instance for a single worker thread.
+pub fn create_db(db: &str, props: Arc<Properties>) -> Result<Rc<dyn DB>> {
+    match db {
+        "sqlite" => Ok(Rc::new(SQLite::new(props)?)),
+        #[cfg(feature = "lmdb")]
+        "lmdb" => Ok(Rc::new(LmdbDB::new(props)?)),
+        #[cfg(not(feature = "lmdb"))]
+        "lmdb" => Err(anyhow!(
+            "lmdb support requires building with --features lmdb"
+        )),
+        #[cfg(feature = "memcached")]
+        "memcached" => Ok(Rc::new(Memcached::new(props)?)),
+        #[cfg(not(feature = "memcached"))]
+        "memcached" => Err(anyhow!(
+            "memcached support requires building with --features memcached"
+        )),
+        #[cfg(feature = "postgres")]
+        "postgres" => Ok(Rc::new(Postgres::new(props)?)),
+        #[cfg(not(feature = "postgres"))]
+        "postgres" => Err(anyhow!(
+            "postgres support requires building with --features postgres"
+        )),
+        #[cfg(feature = "redis")]
+        "redis" => Ok(Rc::new(Redis::new(props)?)),
+        #[cfg(not(feature = "redis"))]
+        "redis" => Err(anyhow!(
+            "redis support requires building with --features redis"
+        )),
+        #[cfg(feature = "rest")]
+        "rest" => Ok(Rc::new(Rest::new(props)?)),
+        #[cfg(not(feature = "rest"))]
+        "rest" => Err(anyhow!(
+            "rest support requires building with --features rest"
+        )),
+        "memory" => Err(anyhow!("memory requires dbinstance = \"shared\"")),
+        #[cfg(feature = "sled")]
+        "sled" => Err(anyhow!("sled requires dbinstance = \"shared\"")),
+        #[cfg(not(feature = "sled"))]
+        "sled" => Err(anyhow!(
+            "sled support requires building with --features sled"
+        )),
+        #[cfg(feature = "rocksdb")]
+        "rocksdb" => Err(anyhow!("rocksdb requires dbinstance = \"shared\"")),
+        #[cfg(not(feature = "rocksdb"))]
+        "rocksdb" => Err(anyhow!(
+            "rocksdb support requires building with --features rocksdb"
+        )),
+        #[cfg(feature = "redb")]
+        "redb" => Err(anyhow!("redb requires dbinstance = \"shared\"")),
+        #[cfg(not(feature = "redb"))]
+        "redb" => Err(anyhow!(
+            "redb support requires building with --features redb"
+        )),
+        db => Err(anyhow!("{} is an invalid database name", db)),
+    }
 }
 
-pub fn create_db(db: &str) -> Result<Rc<dyn DB>> {
+/// The instance kind used unless `dbinstance` is set. Backends that can only
+/// be opened once per process share one instance.
+pub fn default_db_instance(db: &str) -> DBInstance {
     match db {
-        "sqlite" => Ok(Rc::new(SQLite::new()?)),
+        "memory" | "sled" | "rocksdb" | "redb" => DBInstance::Shared,
+        _ => DBInstance::Thread,
+    }
+}
+
+/// Creates one instance that is shared by all worker threads.
+// `props` is only used by backends behind cargo features.
+#[allow(unused_variables)]
+pub fn create_shared_db(db: &str, props: Arc<Properties>) -> Result<Arc<dyn DB + Send + Sync>> {
+    match db {
+        "sqlite" => Err(anyhow!("sqlite does not support a shared instance")),
+        "memory" => Ok(Arc::new(MemoryDB::new()?)),
+        "lmdb" => Err(anyhow!("lmdb does not support a shared instance")),
+        "memcached" => Err(anyhow!("memcached does not support a shared instance")),
+        "postgres" => Err(anyhow!("postgres does not support a shared instance")),
+        "redis" => Err(anyhow!("redis does not support a shared instance")),
+        #[cfg(feature = "rest")]
+        "rest" => Ok(Arc::new(Rest::new(props)?)),
+        #[cfg(not(feature = "rest"))]
+        "rest" => Err(anyhow!(
+            "rest support requires building with --features rest"
+        )),
+        #[cfg(feature = "sled")]
+        "sled" => Ok(Arc::new(SledDB::new(props)?)),
+        #[cfg(not(feature = "sled"))]
+        "sled" => Err(anyhow!(
+            "sled support requires building with --features sled"
+        )),
+        #[cfg(feature = "rocksdb")]
+        "rocksdb" => Ok(Arc::new(RocksDB::new(props)?)),
+        #[cfg(not(feature = "rocksdb"))]
+        "rocksdb" => Err(anyhow!(
+            "rocksdb support requires building with --features rocksdb"
+        )),
+        #[cfg(feature = "redb")]
+        "redb" => Ok(Arc::new(RedbDB::new(props)?)),
+        #[cfg(not(feature = "redb"))]
+        "redb" => Err(anyhow!(
+            "redb support requires building with --features redb"
+        )),
         db => Err(anyhow!("{} is an invalid database name", db)),
     }
 }
+
+#[cfg(test)]
+pub fn values(value: &[u8]) -> HashMap<&'static str, Vec<u8>> {
+    let mut values = HashMap::new();
+    values.insert("field0", value.to_vec());
+    values
+}
+
+/// Checks the operations every backend implements against an empty `table`,
+/// and scans if the backend implements them. Leaves the table empty.
+#[cfg(test)]
+pub fn check_crud(db: &dyn DB, table: &str) {
+    let mut result = HashMap::new();
+    assert_eq!(db.read(table, "a", None, &mut result), Status::NotFound);
+    assert_eq!(db.insert(table, "a", &values(b"1")), Status::Ok);
+    assert_eq!(db.read(table, "a", None, &mut result), Status::Ok);
+    assert_eq!(result.len(), 1);
+    assert_eq!(result["field0"], b"1");
+    assert_eq!(db.update(table, "a", &values(b"2")), Status::Ok);
+    let mut result = HashMap::new();
+    assert_eq!(db.read(table, "a", None, &mut result), Status::Ok);
+    assert_eq!(result["field0"], b"2");
+    assert_eq!(db.insert(table, "b", &values(b"3")), Status::Ok);
+    let mut result = Vec::new();
+    match db.scan(table, "a", 10, &mut result) {
+        Status::NotImplemented => {}
+        status => {
+            assert_eq!(status, Status::Ok);
+            assert_eq!(result.len(), 2);
+            assert_eq!(result[0]["field0"], b"2");
+            assert_eq!(result[1]["field0"], b"3");
+            let mut result = Vec::new();
+            assert_eq!(db.scan(table, "b", 10, &mut result), Status::Ok);
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0]["field0"], b"3");
+        }
+    }
+    let mut record = values(b"4");
+    record.insert("field1", b"5".to_vec());
+    assert_eq!(db.insert(table, "c", &record), Status::Ok);
+    let mut result = HashMap::new();
+    assert_eq!(
+        db.read(table, "c", Some(&["field1"]), &mut result),
+        Status::Ok
+    );
+    assert_eq!(result.len(), 1);
+    assert_eq!(result["field1"], b"5");
+    assert_eq!(db.delete(table, "c"), Status::Ok);
+    assert_eq!(db.delete(table, "a"), Status::Ok);
+    let mut result = HashMap::new();
+    assert_eq!(db.read(table, "a", None, &mut result), Status::NotFound);
+    assert_eq!(db.delete(table, "b"), Status::Ok);
+}
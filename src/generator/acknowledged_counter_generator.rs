@@ -1,4 +1,4 @@
-use super::{CounterGenerator, Generator};
+use super::{CounterGenerator, Generator, NumberGenerator};
 use rand::prelude::*;
 use std::sync::{
     atomic::{AtomicBool, AtomicU64, Ordering},
@@ -67,6 +67,12 @@ impl Generator<u64> for AcknowledgedCounterGenerator {
     }
 }
 
+impl NumberGenerator<u64> for AcknowledgedCounterGenerator {
+    fn mean(&self) -> u64 {
+        self.counter.mean()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,7 +80,7 @@ mod tests {
     #[test]
     fn test_counter() {
         let generator = AcknowledgedCounterGenerator::new(1);
-        let mut rng = SmallRng::from_entropy();
+        let mut rng = SmallRng::seed_from_u64(1);
         assert_eq!(generator.next_value(&mut rng), 1);
         assert_eq!(generator.last_value(), 0);
         assert_eq!(generator.next_value(&mut rng), 2);
@@ -1,6 +1,6 @@
 use rand::prelude::SmallRng;
 
-use super::Generator;
+use super::{Generator, NumberGenerator};
 
 pub struct ConstantGenerator<T: ToString + Clone + Send> {
     value: T,
@@ -17,3 +17,24 @@ impl<T: ToString + Clone + Send> Generator<T> for ConstantGenerator<T> {
         self.value.clone()
     }
 }
+
+impl<T: ToString + Clone + Send> NumberGenerator<T> for ConstantGenerator<T> {
+    fn mean(&self) -> T {
+        self.value.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_mean() {
+        let generator = ConstantGenerator::new(42u64);
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _i in 0..100 {
+            assert_eq!(generator.next_value(&mut rng), generator.mean());
+        }
+    }
+}
@@ -1,22 +1,51 @@
-use super::Generator;
+use super::{Generator, NumberGenerator};
 use rand::prelude::*;
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 pub struct CounterGenerator {
     counter: AtomicU64,
+    count_start: u64,
 }
 
 impl CounterGenerator {
     pub fn new(count_start: u64) -> Self {
         Self {
             counter: AtomicU64::new(count_start),
+            count_start,
         }
     }
 }
 
 impl Generator<u64> for CounterGenerator {
     fn next_value(&self, _rng: &mut SmallRng) -> u64 {
-        self.counter
-            .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+        self.counter.fetch_add(1, Ordering::SeqCst)
+    }
+}
+
+impl NumberGenerator<u64> for CounterGenerator {
+    // Mean of the values handed out so far.
+    fn mean(&self) -> u64 {
+        let next = self.counter.load(Ordering::SeqCst);
+        if next == self.count_start {
+            return self.count_start;
+        }
+        self.count_start + (next - 1 - self.count_start) / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mean() {
+        let generator = CounterGenerator::new(10);
+        let mut rng = SmallRng::seed_from_u64(1);
+        assert_eq!(generator.mean(), 10);
+        let mut sum = 0;
+        for _i in 0..101 {
+            sum += generator.next_value(&mut rng);
+        }
+        assert_eq!(generator.mean(), sum / 101);
     }
 }
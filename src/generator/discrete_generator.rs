@@ -53,7 +53,7 @@ mod tests {
         let weight_pairs = vec![WeightPair::new(0.3, "test"), WeightPair::new(0.7, "b")];
         let generator = DiscreteGenerator::<String>::new(weight_pairs);
         let mut result = std::collections::HashMap::new();
-        let mut rng = SmallRng::from_entropy();
+        let mut rng = SmallRng::seed_from_u64(1);
         for _i in 0..10000 {
             let val = generator.next_value(&mut rng);
             result.entry(val).and_modify(|x| *x += 1).or_insert(1);
@@ -0,0 +1,110 @@
+use super::{Generator, NumberGenerator};
+use anyhow::{anyhow, bail, Result};
+use rand::prelude::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Samples values from a histogram file.
+///
+/// The first line of the file is `BlockSize <n>` and every following line is
+/// a `<bucket> <count>` pair. A sample that falls into bucket `i` yields
+/// `(i + 1) * n`.
+pub struct HistogramGenerator {
+    block_size: u64,
+    buckets: Vec<u64>,
+    area: u64,
+    weighted_area: u64,
+}
+
+impl HistogramGenerator {
+    pub fn new(block_size: u64, buckets: Vec<u64>) -> Result<Self> {
+        let area: u64 = buckets.iter().sum();
+        if area == 0 {
+            bail!("histogram has no samples");
+        }
+        let weighted_area = buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (i as u64 + 1) * count)
+            .sum();
+        Ok(Self {
+            block_size,
+            buckets,
+            area,
+            weighted_area,
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self> {
+        let file = File::open(path)?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> Result<Self> {
+        let mut lines = reader.lines();
+        let header = lines.next().ok_or_else(|| anyhow!("empty histogram"))??;
+        let block_size = match header.split_whitespace().collect::<Vec<_>>()[..] {
+            ["BlockSize", block_size] => block_size.parse()?,
+            _ => bail!("first line of histogram is not the BlockSize"),
+        };
+        let mut buckets = vec![];
+        for line in lines {
+            let line = line?;
+            let (bucket, count) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => continue,
+                [bucket, count] => (bucket.parse::<usize>()?, count.parse::<u64>()?),
+                _ => bail!("malformed histogram line: {}", line),
+            };
+            if bucket >= buckets.len() {
+                buckets.resize(bucket + 1, 0);
+            }
+            buckets[bucket] = count;
+        }
+        Self::new(block_size, buckets)
+    }
+}
+
+impl Generator<u64> for HistogramGenerator {
+    fn next_value(&self, rng: &mut SmallRng) -> u64 {
+        let mut number = rng.gen_range(0..self.area);
+        for (i, count) in self.buckets.iter().enumerate() {
+            if number < *count {
+                return (i as u64 + 1) * self.block_size;
+            }
+            number -= count;
+        }
+        unreachable!();
+    }
+}
+
+impl NumberGenerator<u64> for HistogramGenerator {
+    fn mean(&self) -> u64 {
+        self.weighted_area * self.block_size / self.area
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_from_reader() {
+        let input = "BlockSize\t10\n0\t1\n2\t3\n";
+        let generator = HistogramGenerator::from_reader(input.as_bytes()).unwrap();
+        let mut result = std::collections::HashMap::new();
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _i in 0..10000 {
+            let val = generator.next_value(&mut rng);
+            assert!(val == 10 || val == 30);
+            result.entry(val).and_modify(|x| *x += 1).or_insert(1);
+        }
+        assert_eq!(generator.mean(), 25);
+        // Bucket 2 is three times as likely as bucket 0.
+        assert!((7000..8000).contains(&result[&30]));
+    }
+
+    #[test]
+    fn test_histogram_requires_block_size() {
+        assert!(HistogramGenerator::from_reader("0\t1\n".as_bytes()).is_err());
+    }
+}
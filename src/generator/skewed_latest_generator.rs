@@ -0,0 +1,53 @@
+use super::{AcknowledgedCounterGenerator, Generator, ZipfianGenerator};
+use rand::prelude::*;
+use std::sync::Arc;
+
+// Favours the most recently inserted records, with a Zipfian distribution
+// over their age.
+pub struct SkewedLatestGenerator {
+    basis: Arc<AcknowledgedCounterGenerator>,
+    zipfian: ZipfianGenerator,
+}
+
+impl SkewedLatestGenerator {
+    pub fn new(basis: Arc<AcknowledgedCounterGenerator>) -> Self {
+        let zipfian = ZipfianGenerator::from_items(basis.last_value() + 1);
+        Self { basis, zipfian }
+    }
+}
+
+impl Generator<u64> for SkewedLatestGenerator {
+    fn next_value(&self, rng: &mut SmallRng) -> u64 {
+        let max = self.basis.last_value();
+        max - self.zipfian.next_long(max + 1, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_follows_basis() {
+        let basis = Arc::new(AcknowledgedCounterGenerator::new(10));
+        let generator = SkewedLatestGenerator::new(basis.clone());
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut latest = 0;
+        for _i in 0..10000 {
+            let val = generator.next_value(&mut rng);
+            assert!(val <= 9);
+            latest += (val == 9) as u32;
+        }
+        assert!(latest > 1000, "latest record drawn {} times", latest);
+
+        for _i in 0..10 {
+            basis.acknowledge(basis.next_value(&mut rng));
+        }
+        assert_eq!(basis.last_value(), 19);
+        let max = (0..10000)
+            .map(|_| generator.next_value(&mut rng))
+            .max()
+            .unwrap();
+        assert_eq!(max, 19);
+    }
+}
@@ -26,3 +26,21 @@ impl NumberGenerator<u64> for UniformLongGenerator {
         (self.lower_bound + self.upper_bound) / 2
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mean() {
+        let generator = UniformLongGenerator::new(100, 200);
+        let mut rng = SmallRng::seed_from_u64(1);
+        let samples = 100000;
+        let mut sum = 0;
+        for _i in 0..samples {
+            sum += generator.next_value(&mut rng);
+        }
+        let empirical = sum as f64 / samples as f64;
+        assert!((empirical - generator.mean() as f64).abs() < 1.0);
+    }
+}
@@ -0,0 +1,75 @@
+use rand::distributions::Alphanumeric;
+use rand::prelude::*;
+
+/// Generates field values of a requested length.
+pub enum ValueGenerator {
+    /// Random alphanumeric characters.
+    Alphanumeric,
+    /// Uniformly random bytes, which do not compress.
+    Random,
+    /// A single byte repeated.
+    Constant(u8),
+    /// A byte pattern repeated.
+    Pattern(Vec<u8>),
+    /// Random bytes repeated so that the value compresses by roughly the
+    /// given ratio.
+    Compressible(f64),
+}
+
+impl ValueGenerator {
+    pub fn next_value(&self, len: usize, rng: &mut SmallRng) -> Vec<u8> {
+        match self {
+            ValueGenerator::Alphanumeric => rng.sample_iter(Alphanumeric).take(len).collect(),
+            ValueGenerator::Random => {
+                let mut value = vec![0; len];
+                rng.fill_bytes(&mut value);
+                value
+            }
+            ValueGenerator::Constant(byte) => vec![*byte; len],
+            ValueGenerator::Pattern(pattern) => pattern.iter().copied().cycle().take(len).collect(),
+            ValueGenerator::Compressible(ratio) => {
+                let raw_len = ((len as f64 / ratio).ceil() as usize).clamp(1, len.max(1));
+                let mut raw = vec![0; raw_len];
+                rng.fill_bytes(&mut raw);
+                raw.into_iter().cycle().take(len).collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_lengths() {
+        let generators = vec![
+            ValueGenerator::Alphanumeric,
+            ValueGenerator::Random,
+            ValueGenerator::Constant(b'x'),
+            ValueGenerator::Pattern(b"abc".to_vec()),
+            ValueGenerator::Compressible(4.0),
+        ];
+        let mut rng = SmallRng::seed_from_u64(1);
+        for generator in generators {
+            for len in [0, 1, 7, 100] {
+                assert_eq!(generator.next_value(len, &mut rng).len(), len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pattern() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let value = ValueGenerator::Pattern(b"abc".to_vec()).next_value(7, &mut rng);
+        assert_eq!(value, b"abcabca");
+    }
+
+    #[test]
+    fn test_compressible() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let value = ValueGenerator::Compressible(4.0).next_value(100, &mut rng);
+        assert_eq!(&value[..25], &value[25..50]);
+        assert_eq!(&value[..25], &value[75..]);
+    }
+}
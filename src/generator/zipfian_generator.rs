@@ -1,24 +1,27 @@
 use super::{Generator, NumberGenerator};
 use rand::prelude::*;
+use std::sync::RwLock;
 
 pub const ZIPFIAN_CONSTANT: f64 = 0.99;
 
-#[allow(dead_code)]
+#[derive(Clone, Copy)]
 struct ZipfianParameters {
     alpha: f64,
     zetan: f64,
     eta: f64,
     theta: f64,
     zeta2theta: f64,
+    count_for_zeta: u64,
 }
 
-#[allow(dead_code)]
 pub struct ZipfianGenerator {
     items: u64,
     base: u64,
-    zipfian_constant: f64,
+    mean: u64,
+    // Parameters for `items`, read by `next_value` without locking.
     zipfian_parameters: ZipfianParameters,
-    count_for_zeta: u64,
+    // Recomputed when `next_long` is called with a different item count.
+    grown_parameters: RwLock<ZipfianParameters>,
     allow_item_count_decrease: bool,
 }
 
@@ -62,69 +65,89 @@ impl ZipfianGenerator {
             eta: (1.0 - (2.0 / items as f64).powf(1.0 - theta)) / (1.0 - zeta2theta / zetan),
             theta,
             zeta2theta,
+            count_for_zeta: items,
         };
+        // Expected rank under the Zipfian distribution. This walks every item,
+        // like computing zeta does.
+        let mut sum = 0.0;
+        for i in 0..items {
+            sum += i as f64 / (i as f64 + 1.0).powf(theta);
+        }
         Self {
             items,
             base: min,
-            zipfian_constant,
+            mean: min + (sum / zetan) as u64,
             zipfian_parameters,
-            count_for_zeta: items,
+            grown_parameters: RwLock::new(zipfian_parameters),
             allow_item_count_decrease: false,
         }
     }
 
-    fn next_long(&self, item_count: u64, rng: &mut SmallRng) -> u64 {
-        if item_count != self.count_for_zeta {
-            /*
-            if item_count > self.count_for_zeta {
-                warn!("incrementally recomputing Zipfian distribtion (increase)");
-                self.zipfian_parameters.zetan = zeta_4(
-                    self.count_for_zeta,
-                    item_count,
-                    self.zipfian_parameters.theta,
-                    self.zipfian_parameters.zetan,
-                );
-            }
-            if item_count < self.count_for_zeta && self.allow_item_count_decrease {
-                warn!("incrementally recomputing Zipfian distribtion (decrease). This is slow and should be avoided.");
-                self.zipfian_parameters.zetan = zeta_2(item_count, self.zipfian_parameters.theta);
-            }
-
-            self.count_for_zeta = item_count;
-            self.zipfian_parameters.eta = (1.0
-                - (2.0 / self.items as f64).powf(1.0 - self.zipfian_parameters.theta))
-                / (1.0 - self.zipfian_parameters.zeta2theta / self.zipfian_parameters.zetan);
-            */
-            todo!("change item count after creating zipfian is not yet supported");
+    // Items are drawn from [base, base + item_count), where the item count
+    // may grow over time, e.g. with the number of inserted records.
+    pub fn next_long(&self, item_count: u64, rng: &mut SmallRng) -> u64 {
+        if item_count == self.items {
+            return self.next_with(&self.zipfian_parameters, item_count, rng);
+        }
+        let mut params = *self.grown_parameters.read().unwrap();
+        if item_count != params.count_for_zeta {
+            params = self.set_item_count(item_count);
         }
+        self.next_with(&params, item_count, rng)
+    }
 
+    fn next_with(&self, params: &ZipfianParameters, item_count: u64, rng: &mut SmallRng) -> u64 {
         let u = rng.gen::<f64>();
-        let uz = u * self.zipfian_parameters.zetan;
+        let uz = u * params.zetan;
 
         if uz < 1.0 {
             return self.base;
         }
 
-        if uz < 1.0 + (0.5_f64).powf(self.zipfian_parameters.theta) {
+        if uz < 1.0 + (0.5_f64).powf(params.theta) {
             return self.base + 1;
         }
 
         self.base
-            + (item_count as f64
-                * (self.zipfian_parameters.eta * u - self.zipfian_parameters.eta + 1.0)
-                    .powf(self.zipfian_parameters.alpha)) as u64
+            + (item_count as f64 * (params.eta * u - params.eta + 1.0).powf(params.alpha)) as u64
+    }
+
+    fn set_item_count(&self, item_count: u64) -> ZipfianParameters {
+        let mut params = self.grown_parameters.write().unwrap();
+        // Another thread may have recomputed zeta while we waited.
+        if item_count == params.count_for_zeta {
+            return *params;
+        }
+        // Growing is cheap, zeta is extended by the new items only.
+        if item_count > params.count_for_zeta {
+            params.zetan = zeta_4(
+                params.count_for_zeta,
+                item_count,
+                params.theta,
+                params.zetan,
+            );
+        } else if self.allow_item_count_decrease {
+            log::warn!(
+                "recomputing Zipfian distribution (decrease). This is slow and should be avoided."
+            );
+            params.zetan = zeta_2(item_count, params.theta);
+        }
+        params.count_for_zeta = item_count;
+        params.eta = (1.0 - (2.0 / item_count as f64).powf(1.0 - params.theta))
+            / (1.0 - params.zeta2theta / params.zetan);
+        *params
     }
 }
 
 impl Generator<u64> for ZipfianGenerator {
     fn next_value(&self, rng: &mut SmallRng) -> u64 {
-        self.next_long(self.items, rng)
+        self.next_with(&self.zipfian_parameters, self.items, rng)
     }
 }
 
 impl NumberGenerator<u64> for ZipfianGenerator {
     fn mean(&self) -> u64 {
-        todo!("implement ZipfianGenerator::mean")
+        self.mean
     }
 }
 
@@ -138,7 +161,7 @@ mod tests {
         let max = 10;
         let zipfian = ZipfianGenerator::from_range(min, max);
         let mut result = std::collections::HashMap::new();
-        let mut rng = SmallRng::from_entropy();
+        let mut rng = SmallRng::seed_from_u64(1);
         for _i in 0..100000 {
             let val = zipfian.next_value(&mut rng);
             assert!(val >= min);
@@ -147,4 +170,39 @@ mod tests {
         }
         println!("{:?}", result);
     }
+
+    #[test]
+    fn test_growing_item_count() {
+        let zipfian = ZipfianGenerator::from_items(10);
+        let mut rng = SmallRng::seed_from_u64(1);
+        let max = (0..100000)
+            .map(|_| zipfian.next_long(20, &mut rng))
+            .max()
+            .unwrap();
+        assert!((10..20).contains(&max));
+        assert!((0..100000).all(|_| zipfian.next_value(&mut rng) < 10));
+    }
+
+    #[test]
+    fn test_mean() {
+        let min = 0;
+        let max = 999;
+        let zipfian = ZipfianGenerator::from_range(min, max);
+        let mut rng = SmallRng::seed_from_u64(1);
+        let samples = 100000;
+        let mut sum = 0;
+        for _i in 0..samples {
+            sum += zipfian.next_value(&mut rng);
+        }
+        let empirical = sum as f64 / samples as f64;
+        let expected = zipfian.mean() as f64;
+        // The generator approximates the distribution, so allow for a few
+        // percent of the key range in either direction.
+        assert!(
+            (empirical - expected).abs() < 0.05 * (max - min + 1) as f64,
+            "empirical mean {} too far from {}",
+            empirical,
+            expected
+        );
+    }
 }
@@ -0,0 +1,245 @@
+use crate::db::{error_status, field_selected, Status, DB};
+use crate::properties::Properties;
+
+use anyhow::Result;
+use heed::types::{Bytes, Str};
+use heed::{Database, Env, EnvFlags, EnvOpenOptions, RoTxn};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::sync::Arc;
+
+type Record = HashMap<String, Vec<u8>>;
+
+// Every table is a named LMDB database, so this caps the number of tables.
+const MAX_DBS: u32 = 16;
+
+struct CachedReadTxn {
+    txn: RoTxn<'static>,
+    reads: u64,
+}
+
+// Every table is a named database.
+//
+// LMDB environments are opened once per process and shared by all instances,
+// but each worker thread has its own instance so that it can keep its own
+// read transaction around.
+pub struct LmdbDB {
+    env: Env,
+    databases: RefCell<HashMap<String, Database<Str, Bytes>>>,
+    // LMDB only allows one transaction per thread, so the cached read
+    // transaction is dropped before every write.
+    read_txn: RefCell<Option<CachedReadTxn>>,
+    props: Arc<Properties>,
+}
+
+impl LmdbDB {
+    pub fn new(props: Arc<Properties>) -> Result<Self> {
+        let lmdb_props = &props.lmdb;
+        std::fs::create_dir_all(&lmdb_props.path)?;
+        let mut flags = EnvFlags::empty();
+        if lmdb_props.nosync {
+            flags |= EnvFlags::NO_SYNC;
+        }
+        if lmdb_props.nometasync {
+            flags |= EnvFlags::NO_META_SYNC;
+        }
+        let mut options = EnvOpenOptions::new();
+        options.map_size(lmdb_props.map_size).max_dbs(MAX_DBS);
+        // SAFETY: NOSYNC and NOMETASYNC only trade durability for speed, and
+        // the environment is not modified outside of LMDB.
+        let env = unsafe {
+            options.flags(flags);
+            options.open(&lmdb_props.path)?
+        };
+        Ok(LmdbDB {
+            env,
+            databases: RefCell::new(HashMap::new()),
+            read_txn: RefCell::new(None),
+            props,
+        })
+    }
+
+    fn database(&self, table: &str) -> Result<Database<Str, Bytes>> {
+        if let Some(db) = self.databases.borrow().get(table) {
+            return Ok(*db);
+        }
+        self.read_txn.borrow_mut().take();
+        let mut txn = self.env.write_txn()?;
+        let db = self.env.create_database(&mut txn, Some(table))?;
+        txn.commit()?;
+        self.databases.borrow_mut().insert(table.to_string(), db);
+        Ok(db)
+    }
+
+    // Runs `f` in a read transaction, which is reused for up to
+    // `readtxnreuse` calls.
+    fn with_read_txn<T>(&self, f: impl FnOnce(&RoTxn) -> Result<T>) -> Result<T> {
+        let reuse = self.props.lmdb.read_txn_reuse;
+        let mut read_txn = self.read_txn.borrow_mut();
+        if matches!(&*read_txn, Some(cached) if cached.reads >= reuse) {
+            read_txn.take();
+        }
+        let cached = match &mut *read_txn {
+            Some(cached) => cached,
+            None => read_txn.insert(CachedReadTxn {
+                txn: self.env.clone().static_read_txn()?,
+                reads: 0,
+            }),
+        };
+        cached.reads += 1;
+        f(&cached.txn)
+    }
+
+    fn insert_record(
+        &self,
+        table: &str,
+        key: &str,
+        values: &HashMap<&str, Vec<u8>>,
+    ) -> Result<Status> {
+        let db = self.database(table)?;
+        let value = bincode::serialize(values)?;
+        self.read_txn.borrow_mut().take();
+        let mut txn = self.env.write_txn()?;
+        db.put(&mut txn, key, &value)?;
+        txn.commit()?;
+        Ok(Status::Ok)
+    }
+
+    fn read_record(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut Record,
+    ) -> Result<Status> {
+        let db = self.database(table)?;
+        self.with_read_txn(|txn| match db.get(txn, key)? {
+            Some(value) => {
+                let record = bincode::deserialize::<Record>(value)?;
+                result.extend(
+                    record
+                        .into_iter()
+                        .filter(|(field, _)| field_selected(fields, field)),
+                );
+                Ok(Status::Ok)
+            }
+            None => Ok(Status::NotFound),
+        })
+    }
+
+    fn update_record(
+        &self,
+        table: &str,
+        key: &str,
+        values: &HashMap<&str, Vec<u8>>,
+    ) -> Result<Status> {
+        let db = self.database(table)?;
+        self.read_txn.borrow_mut().take();
+        // Write transactions are serialized, so the read-modify-write is
+        // atomic.
+        let mut txn = self.env.write_txn()?;
+        let mut record = match db.get(&txn, key)? {
+            Some(value) => bincode::deserialize::<Record>(value)?,
+            None => return Ok(Status::NotFound),
+        };
+        for (field, value) in values {
+            record.insert(field.to_string(), value.clone());
+        }
+        let value = bincode::serialize(&record)?;
+        db.put(&mut txn, key, &value)?;
+        txn.commit()?;
+        Ok(Status::Ok)
+    }
+
+    fn delete_record(&self, table: &str, key: &str) -> Result<Status> {
+        let db = self.database(table)?;
+        self.read_txn.borrow_mut().take();
+        let mut txn = self.env.write_txn()?;
+        let found = db.delete(&mut txn, key)?;
+        txn.commit()?;
+        Ok(if found { Status::Ok } else { Status::NotFound })
+    }
+
+    fn scan_records(
+        &self,
+        table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<Record>,
+    ) -> Result<Status> {
+        let db = self.database(table)?;
+        let range = (Bound::Included(start_key), Bound::Unbounded);
+        self.with_read_txn(|txn| {
+            for entry in db.range(txn, &range)?.take(record_count) {
+                let (_, value) = entry?;
+                result.push(bincode::deserialize(value)?);
+            }
+            Ok(Status::Ok)
+        })
+    }
+}
+
+impl DB for LmdbDB {
+    fn init(&self) -> Result<()> {
+        self.database(&self.props.table)?;
+        Ok(())
+    }
+
+    fn cleanup(&self) -> Result<()> {
+        self.read_txn.borrow_mut().take();
+        if self.props.lmdb.nosync || self.props.lmdb.nometasync {
+            self.env.force_sync()?;
+        }
+        Ok(())
+    }
+
+    fn insert(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status(self.insert_record(table, key, values))
+    }
+
+    fn read(&self, table: &str, key: &str, fields: Option<&[&str]>, result: &mut Record) -> Status {
+        error_status(self.read_record(table, key, fields, result))
+    }
+
+    fn update(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status(self.update_record(table, key, values))
+    }
+
+    fn delete(&self, table: &str, key: &str) -> Status {
+        error_status(self.delete_record(table, key))
+    }
+
+    fn scan(
+        &self,
+        table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<Record>,
+    ) -> Status {
+        error_status(self.scan_records(table, start_key, record_count, result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{check_crud, values};
+
+    #[test]
+    fn test_operations() {
+        let dir = std::env::temp_dir().join(format!("ycsb-lmdb-{}", std::process::id()));
+        let mut props: Properties =
+            toml::from_str("operationcount = 1\n[lmdb]\nnosync = true\nreadtxnreuse = 100")
+                .unwrap();
+        props.lmdb.path = dir.to_str().unwrap().to_string();
+        let db = LmdbDB::new(Arc::new(props)).unwrap();
+        db.init().unwrap();
+        check_crud(&db, "usertable");
+        assert_eq!(db.update("usertable", "a", &values(b"1")), Status::NotFound);
+        assert_eq!(db.delete("usertable", "a"), Status::NotFound);
+        db.cleanup().unwrap();
+        drop(db);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,9 +1,9 @@
 use crate::db::DB;
 use crate::workload::Workload;
 use anyhow::{bail, Result};
-use properties::Properties;
+use properties::{DBInstance, Properties};
+use rand::rngs::SmallRng;
 use std::fs;
-use std::rc::Rc;
 use std::sync::Arc;
 use std::thread;
 use std::time::Instant;
@@ -12,8 +12,27 @@ use workload::CoreWorkload;
 
 pub mod db;
 pub mod generator;
+#[cfg(feature = "lmdb")]
+pub mod lmdb;
+pub mod measurements;
+#[cfg(feature = "memcached")]
+pub mod memcached;
+pub mod memory;
+#[cfg(feature = "postgres")]
+pub mod postgres;
 pub mod properties;
+#[cfg(feature = "redb")]
+pub mod redb;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "rest")]
+pub mod rest;
+#[cfg(feature = "rocksdb")]
+pub mod rocksdb;
+#[cfg(feature = "sled")]
+pub mod sled;
 pub mod sqlite;
+pub mod utils;
 pub mod workload;
 
 #[derive(StructOpt, Debug)]
@@ -27,26 +46,60 @@ struct Opt {
     workload: String,
     #[structopt(short, long, default_value = "1")]
     threads: usize,
+    #[structopt(long)]
+    seed: Option<u64>,
 }
 
-fn load(wl: Arc<CoreWorkload>, db: Rc<dyn DB>, operation_count: usize) {
+fn load(wl: &CoreWorkload, db: &dyn DB, operation_count: usize, rng: &mut SmallRng) {
     for _ in 0..operation_count {
-        wl.do_insert(db.clone());
+        wl.do_insert(db, rng);
     }
 }
 
-fn run(wl: Arc<CoreWorkload>, db: Rc<dyn DB>, operation_count: usize) {
+fn run(wl: &CoreWorkload, db: &dyn DB, operation_count: usize, rng: &mut SmallRng) {
     for _ in 0..operation_count {
-        wl.do_transaction(db.clone());
+        wl.do_transaction(db, rng);
     }
 }
 
+// The number of operations of a command, split over all threads.
+fn command_operation_count(cmd: &str, props: &Properties) -> u64 {
+    match cmd {
+        // Like upstream YCSB, load inserts `insertcount` records if it is set.
+        "load" if props.insert_count > 0 => props.insert_count,
+        "load" => props.record_count - props.insert_start,
+        _ => props.operation_count,
+    }
+}
+
+// The first `total % threads` threads do one operation more than the others.
+fn thread_operation_count(total: u64, threads: usize, thread_id: usize) -> usize {
+    let threads = threads as u64;
+    let extra = (thread_id as u64) < total % threads;
+    (total / threads + extra as u64) as usize
+}
+
+fn run_command(cmd: &str, wl: &CoreWorkload, db: &dyn DB, count: usize, rng: &mut SmallRng) {
+    match cmd {
+        "load" => load(wl, db, count, rng),
+        "run" => run(wl, db, count, rng),
+        cmd => panic!("invalid command: {}", cmd),
+    };
+}
+
 fn main() -> Result<()> {
+    // Backends log the errors behind failed operations, override with RUST_LOG.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
     let opt = Opt::from_args();
 
     let raw_props = fs::read_to_string(&opt.workload)?;
 
-    let props: Properties = toml::from_str(&raw_props)?;
+    let mut props: Properties = toml::from_str(&raw_props)?;
+
+    if opt.seed.is_some() {
+        props.seed = opt.seed;
+    }
 
     let props = Arc::new(props);
 
@@ -57,35 +110,93 @@ fn main() -> Result<()> {
     }
 
     let database = opt.database.clone();
-    let thread_operation_count = props.operation_count as usize / opt.threads;
+
+    // A shared instance lives across all commands so that e.g. an embedded
+    // engine is opened only once per process.
+    let db_instance = props
+        .db_instance
+        .unwrap_or_else(|| db::default_db_instance(&database));
+    let shared_db = match db_instance {
+        DBInstance::Thread => None,
+        DBInstance::Shared => {
+            let db = db::create_shared_db(&database, props.clone())?;
+            db.init()?;
+            Some(db)
+        }
+    };
+
     for cmd in opt.commands {
+        let operation_count = command_operation_count(&cmd, &props);
         let start = Instant::now();
         let mut threads = vec![];
-        for _ in 0..opt.threads {
+        for thread_id in 0..opt.threads {
             let database = database.clone();
             let wl = wl.clone();
+            let props = props.clone();
             let cmd = cmd.clone();
-            threads.push(thread::spawn(move || {
-                let db = db::create_db(&database).unwrap();
+            let shared_db = shared_db.clone();
+            let thread_operation_count =
+                thread_operation_count(operation_count, opt.threads, thread_id);
+            threads.push(thread::spawn(move || -> Result<()> {
+                let mut rng = wl.thread_rng(thread_id);
+
+                match shared_db {
+                    Some(db) => run_command(&cmd, &wl, &*db, thread_operation_count, &mut rng),
+                    None => {
+                        let db = db::create_db(&database, props)?;
 
-                db.init().unwrap();
+                        db.init()?;
 
-                match &cmd[..] {
-                    "load" => load(wl.clone(), db, thread_operation_count as usize),
-                    "run" => run(wl.clone(), db, thread_operation_count as usize),
-                    cmd => panic!("invalid command: {}", cmd),
-                };
+                        run_command(&cmd, &wl, &*db, thread_operation_count, &mut rng);
+
+                        db.cleanup()?;
+                    }
+                }
+                Ok(())
             }));
         }
-        for t in threads {
-            let _ = t.join();
+        // Join every thread before failing so that none outlives the run.
+        let mut failed = 0;
+        for (thread_id, t) in threads.into_iter().enumerate() {
+            match t.join() {
+                Ok(Ok(())) => continue,
+                Ok(Err(err)) => eprintln!("thread {}: {:#}", thread_id, err),
+                // The panic message has already been printed by the hook.
+                Err(_) => eprintln!("thread {} panicked", thread_id),
+            }
+            failed += 1;
+        }
+        if failed > 0 {
+            bail!("{} of {} threads failed", failed, opt.threads);
+        }
+        if let Some(db) = &shared_db {
+            db.cleanup()?;
         }
         let runtime = start.elapsed().as_millis();
         println!("[OVERALL], ThreadCount, {}", opt.threads);
         println!("[OVERALL], RunTime(ms), {}", runtime);
-        let throughput = props.operation_count as f64 / (runtime as f64 / 1000.0);
+        let throughput = operation_count as f64 / (runtime as f64 / 1000.0);
         println!("[OVERALL], Throughput(ops/sec), {}", throughput);
+        wl.measurements().report();
+        wl.measurements().reset();
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_inserts_every_record() {
+        let props: Properties =
+            toml::from_str("operationcount = 2000\nrecordcount = 1000").unwrap();
+        assert_eq!(command_operation_count("load", &props), 1000);
+        assert_eq!(command_operation_count("run", &props), 2000);
+        let counts: Vec<usize> = (0..3)
+            .map(|thread_id| thread_operation_count(1000, 3, thread_id))
+            .collect();
+        assert_eq!(counts, vec![334, 333, 333]);
+    }
+}
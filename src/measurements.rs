@@ -0,0 +1,53 @@
+use crate::db::Status;
+use crate::workload::CoreOperation;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Per-operation counts of every returned status.
+pub struct Measurements {
+    counts: Vec<AtomicU64>,
+}
+
+impl Measurements {
+    pub fn new() -> Self {
+        let mut counts = Vec::new();
+        counts.resize_with(CoreOperation::ALL.len() * Status::ALL.len(), || {
+            AtomicU64::new(0)
+        });
+        Self { counts }
+    }
+
+    fn index(op: CoreOperation, status: Status) -> usize {
+        op as usize * Status::ALL.len() + status as usize
+    }
+
+    pub fn record(&self, op: CoreOperation, status: Status) {
+        self.counts[Self::index(op, status)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self, op: CoreOperation, status: Status) -> u64 {
+        self.counts[Self::index(op, status)].load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        for count in &self.counts {
+            count.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn report(&self) {
+        for op in CoreOperation::ALL {
+            for status in Status::ALL {
+                let count = self.count(op, status);
+                if count > 0 {
+                    println!("[{}], Return={}, {}", op.name(), status, count);
+                }
+            }
+        }
+    }
+}
+
+impl Default for Measurements {
+    fn default() -> Self {
+        Self::new()
+    }
+}
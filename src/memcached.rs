@@ -0,0 +1,372 @@
+use crate::db::{error_status_with, field_selected, Status, DB};
+use crate::properties::Properties;
+use crate::utils::fnvhash64_bytes;
+
+use anyhow::{anyhow, bail, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+type Record = HashMap<String, Vec<u8>>;
+
+fn classify(err: &anyhow::Error) -> Status {
+    if err.is::<std::io::Error>() {
+        Status::ServiceUnavailable
+    } else {
+        Status::Error
+    }
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    fn open(server: &str, timeout: Option<Duration>) -> Result<Self> {
+        let addr = server
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("{} does not resolve to an address", server))?;
+        let stream = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(&addr, timeout)?,
+            None => TcpStream::connect(addr)?,
+        };
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        stream.set_nodelay(true)?;
+        Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        let line = line.trim_end().to_string();
+        if line == "ERROR" || line.starts_with("CLIENT_ERROR") || line.starts_with("SERVER_ERROR") {
+            bail!("memcached: {}", line);
+        }
+        Ok(line)
+    }
+
+    // Sends a storage command and returns the reply, e.g. STORED.
+    fn store(
+        &mut self,
+        command: &str,
+        key: &str,
+        value: &[u8],
+        cas: Option<u64>,
+    ) -> Result<String> {
+        let mut request = format!("{} {} 0 0 {}", command, key, value.len());
+        if let Some(cas) = cas {
+            request.push_str(&format!(" {}", cas));
+        }
+        request.push_str("\r\n");
+        let mut request = request.into_bytes();
+        request.extend_from_slice(value);
+        request.extend_from_slice(b"\r\n");
+        self.writer.write_all(&request)?;
+        self.read_line()
+    }
+
+    // Returns the value and its CAS unique, which is only sent by `gets`.
+    fn get(&mut self, command: &str, key: &str) -> Result<Option<(Vec<u8>, Option<u64>)>> {
+        self.writer
+            .write_all(format!("{} {}\r\n", command, key).as_bytes())?;
+        let line = self.read_line()?;
+        if line == "END" {
+            return Ok(None);
+        }
+        // VALUE <key> <flags> <bytes> [<cas unique>]
+        let parts: Vec<&str> = line.split(' ').collect();
+        if parts.len() < 4 || parts[0] != "VALUE" {
+            bail!("memcached: unexpected reply {}", line);
+        }
+        let len: usize = parts[3].parse()?;
+        let cas = parts.get(4).map(|cas| cas.parse()).transpose()?;
+        let mut value = vec![0; len + 2];
+        self.reader.read_exact(&mut value)?;
+        value.truncate(len);
+        let end = self.read_line()?;
+        if end != "END" {
+            bail!("memcached: unexpected reply {}", end);
+        }
+        Ok(Some((value, cas)))
+    }
+
+    fn delete(&mut self, key: &str) -> Result<String> {
+        self.writer
+            .write_all(format!("delete {}\r\n", key).as_bytes())?;
+        self.read_line()
+    }
+}
+
+// Every record lives on the server picked by hashing its key.
+pub struct Memcached {
+    servers: Vec<String>,
+    timeout: Option<Duration>,
+    // Connections are opened lazily and dropped after any error, because the
+    // protocol state is unknown afterwards.
+    connections: RefCell<Vec<Option<Connection>>>,
+}
+
+impl Memcached {
+    pub fn new(props: Arc<Properties>) -> Result<Self> {
+        let memcached_props = &props.memcached;
+        if memcached_props.servers.is_empty() {
+            bail!("memcached requires at least one server");
+        }
+        let timeout = match memcached_props.timeout {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        };
+        Ok(Memcached {
+            servers: memcached_props.servers.clone(),
+            timeout,
+            connections: RefCell::new(memcached_props.servers.iter().map(|_| None).collect()),
+        })
+    }
+
+    fn with_connection<T>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&mut Connection) -> Result<T>,
+    ) -> Result<T> {
+        // A stable hash, so that every run and client picks the same server.
+        let idx = (fnvhash64_bytes(key.as_bytes()) % self.servers.len() as u64) as usize;
+        let mut connections = self.connections.borrow_mut();
+        let conn = match &mut connections[idx] {
+            Some(conn) => conn,
+            conn => conn.insert(Connection::open(&self.servers[idx], self.timeout)?),
+        };
+        let result = f(conn);
+        if result.is_err() {
+            connections[idx] = None;
+        }
+        result
+    }
+
+    // Like upstream YCSB, inserting an existing key fails.
+    fn insert_record(&self, key: &str, values: &HashMap<&str, Vec<u8>>) -> Result<Status> {
+        let value = bincode::serialize(values)?;
+        self.with_connection(key, |conn| {
+            match conn.store("add", key, &value, None)?.as_str() {
+                "STORED" => Ok(Status::Ok),
+                _ => Ok(Status::Error),
+            }
+        })
+    }
+
+    fn read_record(
+        &self,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut Record,
+    ) -> Result<Status> {
+        self.with_connection(key, |conn| match conn.get("get", key)? {
+            Some((value, _)) => {
+                let record = bincode::deserialize::<Record>(&value)?;
+                result.extend(
+                    record
+                        .into_iter()
+                        .filter(|(field, _)| field_selected(fields, field)),
+                );
+                Ok(Status::Ok)
+            }
+            None => Ok(Status::NotFound),
+        })
+    }
+
+    // Merges the fields with `gets` and `cas`, retrying if another client
+    // changed the record in between.
+    fn update_record(&self, key: &str, values: &HashMap<&str, Vec<u8>>) -> Result<Status> {
+        self.with_connection(key, |conn| loop {
+            let (value, cas) = match conn.get("gets", key)? {
+                Some((value, Some(cas))) => (value, cas),
+                Some((_, None)) => bail!("memcached: gets reply without a CAS unique"),
+                None => return Ok(Status::NotFound),
+            };
+            let mut record = bincode::deserialize::<Record>(&value)?;
+            for (field, value) in values {
+                record.insert(field.to_string(), value.clone());
+            }
+            let value = bincode::serialize(&record)?;
+            match conn.store("cas", key, &value, Some(cas))?.as_str() {
+                "STORED" => return Ok(Status::Ok),
+                "NOT_FOUND" => return Ok(Status::NotFound),
+                "EXISTS" => continue,
+                reply => bail!("memcached: unexpected reply {}", reply),
+            }
+        })
+    }
+
+    fn delete_record(&self, key: &str) -> Result<Status> {
+        self.with_connection(key, |conn| match conn.delete(key)?.as_str() {
+            "DELETED" => Ok(Status::Ok),
+            "NOT_FOUND" => Ok(Status::NotFound),
+            reply => bail!("memcached: unexpected reply {}", reply),
+        })
+    }
+}
+
+impl DB for Memcached {
+    fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn insert(&self, _table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status_with(self.insert_record(key, values), classify)
+    }
+
+    fn read(
+        &self,
+        _table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut Record,
+    ) -> Status {
+        error_status_with(self.read_record(key, fields, result), classify)
+    }
+
+    fn update(&self, _table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status_with(self.update_record(key, values), classify)
+    }
+
+    fn delete(&self, _table: &str, key: &str) -> Status {
+        error_status_with(self.delete_record(key), classify)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{check_crud, values};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+
+    // key -> (value, cas unique)
+    type Items = Mutex<HashMap<String, (Vec<u8>, u64)>>;
+
+    // Serves the storage, retrieval and delete commands the backend uses.
+    fn serve(stream: TcpStream, items: &Items, next_cas: &Mutex<u64>) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap_or(0) > 0 {
+            let parts: Vec<String> = line.split_whitespace().map(String::from).collect();
+            line.clear();
+            let mut items = items.lock().unwrap();
+            let reply = match parts[0].as_str() {
+                "add" | "set" | "replace" | "cas" => {
+                    let len: usize = parts[4].parse().unwrap();
+                    let mut value = vec![0; len + 2];
+                    reader.read_exact(&mut value).unwrap();
+                    value.truncate(len);
+                    let key = &parts[1];
+                    let current = items.get(key).map(|(_, cas)| *cas);
+                    let stored = match (parts[0].as_str(), current) {
+                        ("add", Some(_)) | ("replace", None) => "NOT_STORED",
+                        ("cas", None) => "NOT_FOUND",
+                        ("cas", Some(cas)) if parts[5] != cas.to_string() => "EXISTS",
+                        _ => {
+                            let mut next_cas = next_cas.lock().unwrap();
+                            *next_cas += 1;
+                            items.insert(key.clone(), (value, *next_cas));
+                            "STORED"
+                        }
+                    };
+                    format!("{}\r\n", stored).into_bytes()
+                }
+                "get" | "gets" => {
+                    let mut reply = Vec::new();
+                    if let Some((value, cas)) = items.get(&parts[1]) {
+                        let mut header = format!("VALUE {} 0 {}", parts[1], value.len());
+                        if parts[0] == "gets" {
+                            header.push_str(&format!(" {}", cas));
+                        }
+                        reply.extend(format!("{}\r\n", header).as_bytes());
+                        reply.extend(value);
+                        reply.extend(b"\r\n");
+                    }
+                    reply.extend(b"END\r\n");
+                    reply
+                }
+                "delete" => match items.remove(&parts[1]) {
+                    Some(_) => b"DELETED\r\n".to_vec(),
+                    None => b"NOT_FOUND\r\n".to_vec(),
+                },
+                _ => b"ERROR\r\n".to_vec(),
+            };
+            writer.write_all(&reply).unwrap();
+        }
+    }
+
+    fn start_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let items = Arc::new(Items::default());
+            let next_cas = Arc::new(Mutex::new(0));
+            for stream in listener.incoming() {
+                let items = items.clone();
+                let next_cas = next_cas.clone();
+                std::thread::spawn(move || serve(stream.unwrap(), &items, &next_cas));
+            }
+        });
+        addr
+    }
+
+    fn connect(servers: Vec<String>) -> Memcached {
+        let mut props: Properties = toml::from_str("operationcount = 1").unwrap();
+        props.memcached.servers = servers;
+        Memcached::new(Arc::new(props)).unwrap()
+    }
+
+    #[test]
+    fn test_operations() {
+        let db = connect(vec![start_server(), start_server()]);
+        check_crud(&db, "usertable");
+    }
+
+    #[test]
+    fn test_add_and_cas() {
+        let db = connect(vec![start_server(), start_server()]);
+        let table = "usertable";
+        for key in ["a", "b", "c", "d"] {
+            assert_eq!(db.insert(table, key, &values(b"1")), Status::Ok);
+            assert_eq!(db.insert(table, key, &values(b"1")), Status::Error);
+            let mut update = values(b"2");
+            update.insert("field1", b"3".to_vec());
+            assert_eq!(db.update(table, key, &update), Status::Ok);
+            let mut result = HashMap::new();
+            assert_eq!(db.read(table, key, None, &mut result), Status::Ok);
+            assert_eq!(result["field0"], b"2");
+            assert_eq!(result["field1"], b"3");
+            assert_eq!(db.delete(table, key), Status::Ok);
+            assert_eq!(db.delete(table, key), Status::NotFound);
+            assert_eq!(db.update(table, key, &update), Status::NotFound);
+        }
+        // Keys are spread over both servers, so both were connected to.
+        assert!(db.connections.borrow().iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn test_connection_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        let db = connect(vec![addr]);
+        let mut result = HashMap::new();
+        assert_eq!(
+            db.read("usertable", "a", None, &mut result),
+            Status::ServiceUnavailable
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::db::{field_selected, Status, DB};
+
+use anyhow::Result;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
+
+const SHARDS: usize = 64;
+
+type Record = HashMap<String, Vec<u8>>;
+
+#[derive(Default)]
+struct Store {
+    tables: RwLock<HashMap<String, Arc<Table>>>,
+}
+
+// Keys are spread over shards by hash, and every shard is ordered so that
+// scans can merge the shards.
+struct Table {
+    shards: Vec<RwLock<BTreeMap<String, Record>>>,
+}
+
+impl Table {
+    fn new() -> Self {
+        let mut shards = Vec::with_capacity(SHARDS);
+        shards.resize_with(SHARDS, Default::default);
+        Self { shards }
+    }
+
+    fn shard(&self, key: &str) -> &RwLock<BTreeMap<String, Record>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+}
+
+impl Store {
+    fn table(&self, table: &str) -> Option<Arc<Table>> {
+        self.tables.read().unwrap().get(table).cloned()
+    }
+
+    fn table_or_create(&self, table: &str) -> Arc<Table> {
+        if let Some(table) = self.table(table) {
+            return table;
+        }
+        self.tables
+            .write()
+            .unwrap()
+            .entry(table.to_string())
+            .or_insert_with(|| Arc::new(Table::new()))
+            .clone()
+    }
+}
+
+// Every instance has its own store, so all threads must share one instance.
+pub struct MemoryDB {
+    store: Store,
+}
+
+impl MemoryDB {
+    pub fn new() -> Result<Self> {
+        Ok(MemoryDB {
+            store: Store::default(),
+        })
+    }
+}
+
+impl DB for MemoryDB {
+    fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn insert(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        let table = self.store.table_or_create(table);
+        let record = values
+            .iter()
+            .map(|(field, value)| (field.to_string(), value.clone()))
+            .collect();
+        table
+            .shard(key)
+            .write()
+            .unwrap()
+            .insert(key.to_string(), record);
+        Status::Ok
+    }
+
+    fn read(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut HashMap<String, Vec<u8>>,
+    ) -> Status {
+        let table = match self.store.table(table) {
+            Some(table) => table,
+            None => return Status::NotFound,
+        };
+        let shard = table.shard(key).read().unwrap();
+        match shard.get(key) {
+            Some(record) => {
+                result.extend(
+                    record
+                        .iter()
+                        .filter(|(field, _)| field_selected(fields, field))
+                        .map(|(k, v)| (k.clone(), v.clone())),
+                );
+                Status::Ok
+            }
+            None => Status::NotFound,
+        }
+    }
+
+    fn update(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        let table = match self.store.table(table) {
+            Some(table) => table,
+            None => return Status::NotFound,
+        };
+        let mut shard = table.shard(key).write().unwrap();
+        match shard.get_mut(key) {
+            Some(record) => {
+                for (field, value) in values {
+                    record.insert(field.to_string(), value.clone());
+                }
+                Status::Ok
+            }
+            None => Status::NotFound,
+        }
+    }
+
+    fn delete(&self, table: &str, key: &str) -> Status {
+        let table = match self.store.table(table) {
+            Some(table) => table,
+            None => return Status::NotFound,
+        };
+        let mut shard = table.shard(key).write().unwrap();
+        match shard.remove(key) {
+            Some(_) => Status::Ok,
+            None => Status::NotFound,
+        }
+    }
+
+    fn scan(
+        &self,
+        table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<HashMap<String, Vec<u8>>>,
+    ) -> Status {
+        let table = match self.store.table(table) {
+            Some(table) => table,
+            None => return Status::Ok,
+        };
+        // Take the first `record_count` keys of every shard, keep the smallest
+        // keys overall and only clone their records. Writers lock a single
+        // shard, so holding all read locks at once cannot deadlock.
+        let shards: Vec<_> = table
+            .shards
+            .iter()
+            .map(|shard| shard.read().unwrap())
+            .collect();
+        let mut records: Vec<(&String, &Record)> = Vec::new();
+        for shard in &shards {
+            records.extend(shard.range(start_key.to_string()..).take(record_count));
+        }
+        records.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        result.extend(
+            records
+                .into_iter()
+                .take(record_count)
+                .map(|(_, record)| record.clone()),
+        );
+        Status::Ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{check_crud, values};
+
+    #[test]
+    fn test_operations() {
+        let db = MemoryDB::new().unwrap();
+        check_crud(&db, "usertable");
+        assert_eq!(db.update("usertable", "a", &values(b"1")), Status::NotFound);
+        assert_eq!(db.delete("usertable", "a"), Status::NotFound);
+    }
+
+    #[test]
+    fn test_scan() {
+        let db = MemoryDB::new().unwrap();
+        let table = "test_scan";
+        for i in 0..100 {
+            let key = format!("key{:03}", i);
+            assert_eq!(db.insert(table, &key, &values(key.as_bytes())), Status::Ok);
+        }
+        let mut result = Vec::new();
+        assert_eq!(db.scan(table, "key042", 5, &mut result), Status::Ok);
+        let keys: Vec<&[u8]> = result.iter().map(|r| &r["field0"][..]).collect();
+        let expected: Vec<&[u8]> = vec![b"key042", b"key043", b"key044", b"key045", b"key046"];
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn test_separate_instances() {
+        let db = MemoryDB::new().unwrap();
+        assert_eq!(db.insert("usertable", "a", &values(b"1")), Status::Ok);
+        let other = MemoryDB::new().unwrap();
+        let mut result = HashMap::new();
+        assert_eq!(
+            other.read("usertable", "a", None, &mut result),
+            Status::NotFound
+        );
+    }
+}
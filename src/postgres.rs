@@ -0,0 +1,361 @@
+use crate::db::{error_status_with, Status, DB};
+use crate::properties::Properties;
+
+use anyhow::{anyhow, Result};
+use postgres::error::SqlState;
+use postgres::types::ToSql;
+use postgres::{Client, NoTls, Row, Statement};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+const PRIMARY_KEY: &str = "y_id";
+
+// Every worker thread opens its own connection and calls `init`, but the
+// table must only be dropped and created once per process. Holds the error,
+// if any, for every later thread.
+static CREATE_TABLE: OnceLock<Result<(), String>> = OnceLock::new();
+
+fn classify(err: &anyhow::Error) -> Status {
+    let err = match err.downcast_ref::<postgres::Error>() {
+        Some(err) => err,
+        None => return Status::Error,
+    };
+    match err.code() {
+        Some(&SqlState::UNIQUE_VIOLATION) => Status::Constraint,
+        Some(&SqlState::LOCK_NOT_AVAILABLE) => Status::Locked,
+        Some(&SqlState::T_R_SERIALIZATION_FAILURE) | Some(&SqlState::T_R_DEADLOCK_DETECTED) => {
+            Status::Busy
+        }
+        None if err.is_closed() => Status::ServiceUnavailable,
+        _ => Status::Error,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Insert,
+    Read,
+    Update,
+    Delete,
+    Scan,
+}
+
+struct CachedStatement {
+    op: Operation,
+    table: String,
+    fields: Vec<String>,
+    stmt: Statement,
+}
+
+pub struct Postgres {
+    client: RefCell<Client>,
+    // Prepared statements are per connection. There is only a handful of
+    // distinct statements, so a linear scan is cheaper than hashing.
+    statements: RefCell<Vec<CachedStatement>>,
+    props: Arc<Properties>,
+}
+
+impl Postgres {
+    pub fn new(props: Arc<Properties>) -> Result<Self> {
+        let client = Client::connect(&props.postgres.url, NoTls)?;
+        Ok(Postgres {
+            client: RefCell::new(client),
+            statements: RefCell::new(Vec::new()),
+            props,
+        })
+    }
+
+    fn statement(
+        &self,
+        op: Operation,
+        table: &str,
+        fields: &[&str],
+        sql: impl FnOnce() -> String,
+    ) -> Result<Statement> {
+        let mut statements = self.statements.borrow_mut();
+        if let Some(cached) = statements
+            .iter()
+            .find(|cached| cached.op == op && cached.table == table && cached.fields == fields)
+        {
+            return Ok(cached.stmt.clone());
+        }
+        let stmt = self.client.borrow_mut().prepare(&sql())?;
+        statements.push(CachedStatement {
+            op,
+            table: table.to_string(),
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            stmt: stmt.clone(),
+        });
+        Ok(stmt)
+    }
+
+    fn create_table(&self) -> Result<()> {
+        let mut client = self.client.borrow_mut();
+        let table = &self.props.table;
+        if self.props.postgres.drop_table {
+            client.batch_execute(&format!("DROP TABLE IF EXISTS {}", table))?;
+        }
+        let mut columns = vec![format!("{} VARCHAR PRIMARY KEY", PRIMARY_KEY)];
+        for field in self.props.field_names() {
+            columns.push(format!("{} BYTEA", field));
+        }
+        client.batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            table,
+            columns.join(", ")
+        ))?;
+        Ok(())
+    }
+
+    // Reads the field columns of a row, skipping NULLs.
+    fn read_fields(row: &Row, result: &mut HashMap<String, Vec<u8>>) -> Result<()> {
+        for (idx, column) in row.columns().iter().enumerate() {
+            if let Some(value) = row.try_get::<_, Option<Vec<u8>>>(idx)? {
+                result.insert(column.name().to_string(), value);
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_row(
+        &self,
+        table: &str,
+        key: &str,
+        values: &HashMap<&str, Vec<u8>>,
+    ) -> Result<Status> {
+        let mut fields: Vec<&str> = values.keys().copied().collect();
+        fields.sort_unstable();
+        let stmt = self.statement(Operation::Insert, table, &fields, || {
+            let placeholders: Vec<String> = (1..=fields.len() + 1)
+                .map(|idx| format!("${}", idx))
+                .collect();
+            let mut sql = format!(
+                "INSERT INTO {} ({}, {}) VALUES ({})",
+                table,
+                PRIMARY_KEY,
+                fields.join(", "),
+                placeholders.join(", ")
+            );
+            if self.props.postgres.upsert {
+                let updates: Vec<String> = fields
+                    .iter()
+                    .map(|field| format!("{} = EXCLUDED.{}", field, field))
+                    .collect();
+                sql.push_str(&format!(
+                    " ON CONFLICT ({}) DO UPDATE SET {}",
+                    PRIMARY_KEY,
+                    updates.join(", ")
+                ));
+            }
+            sql
+        })?;
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&key];
+        for field in &fields {
+            params.push(&values[field]);
+        }
+        self.client.borrow_mut().execute(&stmt, &params)?;
+        Ok(Status::Ok)
+    }
+
+    fn read_row(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut HashMap<String, Vec<u8>>,
+    ) -> Result<Status> {
+        // Reading all fields is cached like an empty selection.
+        let fields = fields.unwrap_or(&[]);
+        let stmt = self.statement(Operation::Read, table, fields, || {
+            let columns = match fields {
+                [] => self.props.field_names().join(", "),
+                fields => fields.join(", "),
+            };
+            format!(
+                "SELECT {} FROM {} WHERE {} = $1",
+                columns, table, PRIMARY_KEY
+            )
+        })?;
+        match self.client.borrow_mut().query_opt(&stmt, &[&key])? {
+            Some(row) => {
+                Self::read_fields(&row, result)?;
+                Ok(Status::Ok)
+            }
+            None => Ok(Status::NotFound),
+        }
+    }
+
+    fn update_row(
+        &self,
+        table: &str,
+        key: &str,
+        values: &HashMap<&str, Vec<u8>>,
+    ) -> Result<Status> {
+        let mut fields: Vec<&str> = values.keys().copied().collect();
+        fields.sort_unstable();
+        let stmt = self.statement(Operation::Update, table, &fields, || {
+            let updates: Vec<String> = fields
+                .iter()
+                .enumerate()
+                .map(|(idx, field)| format!("{} = ${}", field, idx + 2))
+                .collect();
+            format!(
+                "UPDATE {} SET {} WHERE {} = $1",
+                table,
+                updates.join(", "),
+                PRIMARY_KEY
+            )
+        })?;
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&key];
+        for field in &fields {
+            params.push(&values[field]);
+        }
+        match self.client.borrow_mut().execute(&stmt, &params)? {
+            0 => Ok(Status::NotFound),
+            _ => Ok(Status::Ok),
+        }
+    }
+
+    fn delete_row(&self, table: &str, key: &str) -> Result<Status> {
+        let stmt = self.statement(Operation::Delete, table, &[], || {
+            format!("DELETE FROM {} WHERE {} = $1", table, PRIMARY_KEY)
+        })?;
+        match self.client.borrow_mut().execute(&stmt, &[&key])? {
+            0 => Ok(Status::NotFound),
+            _ => Ok(Status::Ok),
+        }
+    }
+
+    fn scan_rows(
+        &self,
+        table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<HashMap<String, Vec<u8>>>,
+    ) -> Result<Status> {
+        let stmt = self.statement(Operation::Scan, table, &[], || {
+            format!(
+                "SELECT {} FROM {} WHERE {} >= $1 ORDER BY {} LIMIT $2",
+                self.props.field_names().join(", "),
+                table,
+                PRIMARY_KEY,
+                PRIMARY_KEY
+            )
+        })?;
+        let limit = record_count as i64;
+        for row in self
+            .client
+            .borrow_mut()
+            .query(&stmt, &[&start_key, &limit])?
+        {
+            let mut record = HashMap::new();
+            Self::read_fields(&row, &mut record)?;
+            result.push(record);
+        }
+        Ok(Status::Ok)
+    }
+}
+
+impl DB for Postgres {
+    fn init(&self) -> Result<()> {
+        CREATE_TABLE
+            .get_or_init(|| self.create_table().map_err(|err| format!("{:#}", err)))
+            .clone()
+            .map_err(|err| anyhow!("creating table {} failed: {}", self.props.table, err))
+    }
+
+    fn insert(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status_with(self.insert_row(table, key, values), classify)
+    }
+
+    fn read(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut HashMap<String, Vec<u8>>,
+    ) -> Status {
+        error_status_with(self.read_row(table, key, fields, result), classify)
+    }
+
+    fn update(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status_with(self.update_row(table, key, values), classify)
+    }
+
+    fn delete(&self, table: &str, key: &str) -> Status {
+        error_status_with(self.delete_row(table, key), classify)
+    }
+
+    fn scan(
+        &self,
+        table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<HashMap<String, Vec<u8>>>,
+    ) -> Status {
+        error_status_with(
+            self.scan_rows(table, start_key, record_count, result),
+            classify,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{check_crud, values};
+
+    // The tests need a server, run them with e.g.
+    // YCSB_POSTGRES_URL=postgresql://postgres@localhost/postgres \
+    //     cargo test --features postgres -- --ignored
+    //
+    // Every test uses its own table, which is created directly because `init`
+    // creates a table only once per process.
+    fn connect(table: &str, upsert: bool) -> Postgres {
+        let url = std::env::var("YCSB_POSTGRES_URL").expect("YCSB_POSTGRES_URL is not set");
+        let mut props: Properties =
+            toml::from_str("operationcount = 1\nfieldcount = 2\n[postgres]\ndroptable = true")
+                .unwrap();
+        props.table = table.to_string();
+        props.postgres.url = url;
+        props.postgres.upsert = upsert;
+        let db = Postgres::new(Arc::new(props)).unwrap();
+        db.create_table().unwrap();
+        db
+    }
+
+    fn drop_table(db: Postgres, table: &str) {
+        db.client
+            .borrow_mut()
+            .batch_execute(&format!("DROP TABLE {}", table))
+            .unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn test_operations() {
+        let table = "ycsb_postgres_test_operations";
+        let db = connect(table, false);
+        check_crud(&db, table);
+        assert_eq!(db.insert(table, "a", &values(b"1")), Status::Ok);
+        assert_eq!(db.insert(table, "a", &values(b"1")), Status::Constraint);
+        assert_eq!(db.update(table, "b", &values(b"2")), Status::NotFound);
+        assert_eq!(db.delete(table, "a"), Status::Ok);
+        assert_eq!(db.delete(table, "a"), Status::NotFound);
+        drop_table(db, table);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_upsert() {
+        let table = "ycsb_postgres_test_upsert";
+        let db = connect(table, true);
+        assert_eq!(db.insert(table, "a", &values(b"1")), Status::Ok);
+        assert_eq!(db.insert(table, "a", &values(b"2")), Status::Ok);
+        let mut result = HashMap::new();
+        assert_eq!(db.read(table, "a", None, &mut result), Status::Ok);
+        assert_eq!(result["field0"], b"2");
+        drop_table(db, table);
+    }
+}
@@ -4,6 +4,18 @@ fn zero_u64() -> u64 {
     0
 }
 
+fn table_default() -> String {
+    "usertable".to_string()
+}
+
+fn field_count_default() -> u64 {
+    10
+}
+
+fn batch_size_default() -> usize {
+    1
+}
+
 fn thread_count_default() -> u64 {
     200
 }
@@ -12,6 +24,22 @@ fn field_length_distribution_default() -> String {
     "constant".to_string()
 }
 
+fn field_length_histogram_file_default() -> String {
+    "hist.txt".to_string()
+}
+
+fn value_generator_default() -> String {
+    "alphanumeric".to_string()
+}
+
+fn value_pattern_default() -> String {
+    "ycsb".to_string()
+}
+
+fn value_compression_ratio_default() -> f64 {
+    2.0
+}
+
 fn request_distribution_default() -> String {
     "uniform".to_string()
 }
@@ -40,6 +68,273 @@ fn read_modify_write_proportion_default() -> f64 {
     0.0
 }
 
+fn read_all_fields_default() -> bool {
+    true
+}
+
+fn min_scan_length_default() -> u64 {
+    1
+}
+
+fn max_scan_length_default() -> u64 {
+    1000
+}
+
+fn scan_length_distribution_default() -> String {
+    "uniform".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DBInstance {
+    // every worker thread creates its own instance
+    Thread,
+    // all worker threads share one instance
+    Shared,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct SQLitePragmas {
+    pub page_size: Option<u64>,
+    pub journal_mode: Option<String>,
+    pub synchronous: Option<String>,
+    pub cache_size: Option<i64>,
+    pub mmap_size: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct SQLiteProperties {
+    pub path: String,
+    // milliseconds
+    pub busy_timeout: usize,
+    // create the database file if it does not exist
+    pub create: bool,
+    // open the database as a URI with `cache=shared`, which lets every
+    // connection see the same `:memory:` database
+    #[serde(rename = "sharedcache")]
+    pub shared_cache: bool,
+    // drop and recreate the table once per process in `init`
+    #[serde(rename = "droptable")]
+    pub drop_table: bool,
+    pub pragmas: SQLitePragmas,
+}
+
+impl Default for SQLiteProperties {
+    fn default() -> Self {
+        Self {
+            path: "test.db".to_string(),
+            busy_timeout: 5000,
+            create: true,
+            shared_cache: false,
+            drop_table: false,
+            pragmas: SQLitePragmas::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct SledProperties {
+    pub path: String,
+    // bytes
+    #[serde(rename = "cachecapacity")]
+    pub cache_capacity: u64,
+    // milliseconds, 0 disables periodic flushing
+    #[serde(rename = "flusheveryms")]
+    pub flush_every_ms: u64,
+}
+
+impl Default for SledProperties {
+    fn default() -> Self {
+        Self {
+            path: "sled.db".to_string(),
+            cache_capacity: 1024 * 1024 * 1024,
+            flush_every_ms: 500,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct RocksDBProperties {
+    pub path: String,
+    // defaults to the table name
+    #[serde(rename = "columnfamily")]
+    pub column_family: Option<String>,
+    // fsync the WAL on every write
+    pub sync: bool,
+    #[serde(rename = "disablewal")]
+    pub disable_wal: bool,
+    // bytes
+    #[serde(rename = "blockcachesize")]
+    pub block_cache_size: usize,
+    // none, lz4 or zstd
+    pub compression: String,
+}
+
+impl Default for RocksDBProperties {
+    fn default() -> Self {
+        Self {
+            path: "rocksdb".to_string(),
+            column_family: None,
+            sync: false,
+            disable_wal: false,
+            block_cache_size: 8 * 1024 * 1024,
+            compression: "lz4".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct RedbProperties {
+    pub path: String,
+    // none, eventual or immediate, applied to every write transaction
+    pub durability: String,
+}
+
+impl Default for RedbProperties {
+    fn default() -> Self {
+        Self {
+            path: "redb.db".to_string(),
+            durability: "immediate".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct LmdbProperties {
+    pub path: String,
+    // bytes, must be a multiple of the page size
+    #[serde(rename = "mapsize")]
+    pub map_size: usize,
+    pub nosync: bool,
+    pub nometasync: bool,
+    // number of reads served by one read transaction, 1 disables reuse
+    #[serde(rename = "readtxnreuse")]
+    pub read_txn_reuse: u64,
+}
+
+impl Default for LmdbProperties {
+    fn default() -> Self {
+        Self {
+            path: "lmdb".to_string(),
+            map_size: 1024 * 1024 * 1024,
+            nosync: false,
+            nometasync: false,
+            read_txn_reuse: 1,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct PostgresProperties {
+    // connection string, either a URL or key=value pairs
+    pub url: String,
+    #[serde(rename = "droptable")]
+    pub drop_table: bool,
+    // turn inserts of existing keys into updates
+    pub upsert: bool,
+}
+
+impl Default for PostgresProperties {
+    fn default() -> Self {
+        Self {
+            url: "postgresql://postgres@localhost/postgres".to_string(),
+            drop_table: false,
+            upsert: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct RedisProperties {
+    pub host: String,
+    pub port: u16,
+    pub password: Option<String>,
+}
+
+impl Default for RedisProperties {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 6379,
+            password: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct RestEndpoint {
+    // defaults to POST, GET, PUT and DELETE for insert, read, update and
+    // delete
+    pub method: Option<String>,
+    // `{table}` and `{key}` are substituted
+    pub url: String,
+}
+
+impl Default for RestEndpoint {
+    fn default() -> Self {
+        Self {
+            method: None,
+            url: "http://localhost:8080/{table}/{key}".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct RestProperties {
+    pub insert: RestEndpoint,
+    pub read: RestEndpoint,
+    pub update: RestEndpoint,
+    pub delete: RestEndpoint,
+    pub keepalive: bool,
+    // milliseconds, 0 disables the timeout
+    #[serde(rename = "connecttimeout")]
+    pub connect_timeout: u64,
+    // milliseconds for the whole request, 0 disables the timeout
+    pub timeout: u64,
+}
+
+impl Default for RestProperties {
+    fn default() -> Self {
+        Self {
+            insert: RestEndpoint::default(),
+            read: RestEndpoint::default(),
+            update: RestEndpoint::default(),
+            delete: RestEndpoint::default(),
+            keepalive: true,
+            connect_timeout: 10000,
+            timeout: 10000,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct MemcachedProperties {
+    // host:port, records are spread across them by key hash
+    pub servers: Vec<String>,
+    // milliseconds for connecting and for every read and write, 0 disables
+    // the timeout
+    pub timeout: u64,
+}
+
+impl Default for MemcachedProperties {
+    fn default() -> Self {
+        Self {
+            servers: vec!["localhost:11211".to_string()],
+            timeout: 1000,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Properties {
     #[serde(default = "zero_u64", rename = "insertstart")]
@@ -50,18 +345,32 @@ pub struct Properties {
     pub operation_count: u64,
     #[serde(default = "zero_u64", rename = "recordcount")]
     pub record_count: u64,
+    #[serde(default = "table_default")]
+    pub table: String,
+    #[serde(default = "field_count_default", rename = "fieldcount")]
+    pub field_count: u64,
+    #[serde(default, rename = "dbinstance")]
+    pub db_instance: Option<DBInstance>,
+    #[serde(default = "batch_size_default", rename = "batchsize")]
+    pub batch_size: usize,
     #[serde(default = "thread_count_default", rename = "threacount")]
     pub thread_count: u64,
     #[serde(rename = "maxexecutiontime")]
     pub max_execution_time: Option<u64>,
     #[serde(rename = "warmuptime")]
     pub warmup_time: Option<u64>,
+    pub seed: Option<u64>,
     // field length
     #[serde(
         default = "field_length_distribution_default",
         rename = "fieldlengthdistribution"
     )]
     pub field_length_distribution: String,
+    #[serde(
+        default = "field_length_histogram_file_default",
+        rename = "fieldlengthhistogram"
+    )]
+    pub field_length_histogram_file: String,
     #[serde(
         default = "request_distribution_default",
         rename = "requestdistribution"
@@ -69,6 +378,17 @@ pub struct Properties {
     pub request_distribution: String,
     #[serde(default = "field_length_default", rename = "fieldlength")]
     pub field_length: u64,
+    #[serde(default = "value_generator_default", rename = "valuegenerator")]
+    pub value_generator: String,
+    #[serde(default, rename = "valueconstantbyte")]
+    pub value_constant_byte: u8,
+    #[serde(default = "value_pattern_default", rename = "valuepattern")]
+    pub value_pattern: String,
+    #[serde(
+        default = "value_compression_ratio_default",
+        rename = "valuecompressionratio"
+    )]
+    pub value_compression_ratio: f64,
 
     // read, update, insert, scan, read-modify-write
     #[serde(default = "read_proportion_default", rename = "readproportion")]
@@ -84,4 +404,46 @@ pub struct Properties {
         rename = "readmodifywriteproportion"
     )]
     pub read_modify_write_proportion: f64,
+    #[serde(default = "read_all_fields_default", rename = "readallfields")]
+    pub read_all_fields: bool,
+    #[serde(default, rename = "writeallfields")]
+    pub write_all_fields: bool,
+
+    // scan length
+    #[serde(default = "min_scan_length_default", rename = "minscanlength")]
+    pub min_scan_length: u64,
+    #[serde(default = "max_scan_length_default", rename = "maxscanlength")]
+    pub max_scan_length: u64,
+    #[serde(
+        default = "scan_length_distribution_default",
+        rename = "scanlengthdistribution"
+    )]
+    pub scan_length_distribution: String,
+
+    #[serde(default)]
+    pub sqlite: SQLiteProperties,
+    #[serde(default)]
+    pub sled: SledProperties,
+    #[serde(default)]
+    pub rocksdb: RocksDBProperties,
+    #[serde(default)]
+    pub redb: RedbProperties,
+    #[serde(default)]
+    pub lmdb: LmdbProperties,
+    #[serde(default)]
+    pub postgres: PostgresProperties,
+    #[serde(default)]
+    pub redis: RedisProperties,
+    #[serde(default)]
+    pub rest: RestProperties,
+    #[serde(default)]
+    pub memcached: MemcachedProperties,
+}
+
+impl Properties {
+    pub fn field_names(&self) -> Vec<String> {
+        (0..self.field_count)
+            .map(|i| format!("field{}", i))
+            .collect()
+    }
 }
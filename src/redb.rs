@@ -0,0 +1,214 @@
+use crate::db::{error_status, field_selected, Status, DB};
+use crate::properties::Properties;
+
+use anyhow::{anyhow, Result};
+use redb::{
+    Database, Durability, ReadOnlyTable, ReadableTable, TableDefinition, TableError,
+    WriteTransaction,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type Record = HashMap<String, Vec<u8>>;
+
+// Every table is a redb table.
+pub struct RedbDB {
+    db: Database,
+    durability: Durability,
+}
+
+impl RedbDB {
+    pub fn new(props: Arc<Properties>) -> Result<Self> {
+        let redb_props = &props.redb;
+        let db = Database::create(&redb_props.path)?;
+        Ok(RedbDB {
+            db,
+            durability: parse_durability(&redb_props.durability)?,
+        })
+    }
+
+    fn begin_write(&self) -> Result<WriteTransaction> {
+        let mut txn = self.db.begin_write()?;
+        txn.set_durability(self.durability);
+        Ok(txn)
+    }
+
+    // Returns `None` if nothing has been written to the table yet.
+    fn open_read_table(
+        &self,
+        table: &str,
+    ) -> Result<Option<ReadOnlyTable<&'static str, &'static [u8]>>> {
+        let txn = self.db.begin_read()?;
+        match txn.open_table(TableDefinition::<&str, &[u8]>::new(table)) {
+            Ok(table) => Ok(Some(table)),
+            Err(TableError::TableDoesNotExist(_)) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn insert_record(
+        &self,
+        table: &str,
+        key: &str,
+        values: &HashMap<&str, Vec<u8>>,
+    ) -> Result<Status> {
+        let value = bincode::serialize(values)?;
+        let txn = self.begin_write()?;
+        {
+            let mut table = txn.open_table(TableDefinition::<&str, &[u8]>::new(table))?;
+            table.insert(key, &value[..])?;
+        }
+        txn.commit()?;
+        Ok(Status::Ok)
+    }
+
+    fn read_record(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut Record,
+    ) -> Result<Status> {
+        let table = match self.open_read_table(table)? {
+            Some(table) => table,
+            None => return Ok(Status::NotFound),
+        };
+        match table.get(key)? {
+            Some(value) => {
+                let record = bincode::deserialize::<Record>(value.value())?;
+                result.extend(
+                    record
+                        .into_iter()
+                        .filter(|(field, _)| field_selected(fields, field)),
+                );
+                Ok(Status::Ok)
+            }
+            None => Ok(Status::NotFound),
+        }
+    }
+
+    fn update_record(
+        &self,
+        table: &str,
+        key: &str,
+        values: &HashMap<&str, Vec<u8>>,
+    ) -> Result<Status> {
+        // Write transactions are serialized, so the read-modify-write is
+        // atomic.
+        let txn = self.begin_write()?;
+        {
+            let mut table = txn.open_table(TableDefinition::<&str, &[u8]>::new(table))?;
+            let mut record = match table.get(key)? {
+                Some(value) => bincode::deserialize::<Record>(value.value())?,
+                None => return Ok(Status::NotFound),
+            };
+            for (field, value) in values {
+                record.insert(field.to_string(), value.clone());
+            }
+            let value = bincode::serialize(&record)?;
+            table.insert(key, &value[..])?;
+        }
+        txn.commit()?;
+        Ok(Status::Ok)
+    }
+
+    fn delete_record(&self, table: &str, key: &str) -> Result<Status> {
+        let txn = self.begin_write()?;
+        let found = {
+            let mut table = txn.open_table(TableDefinition::<&str, &[u8]>::new(table))?;
+            let removed = table.remove(key)?;
+            removed.is_some()
+        };
+        txn.commit()?;
+        Ok(if found { Status::Ok } else { Status::NotFound })
+    }
+
+    fn scan_records(
+        &self,
+        table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<Record>,
+    ) -> Result<Status> {
+        let table = match self.open_read_table(table)? {
+            Some(table) => table,
+            None => return Ok(Status::Ok),
+        };
+        for entry in table.range(start_key..)?.take(record_count) {
+            let (_, value) = entry?;
+            result.push(bincode::deserialize(value.value())?);
+        }
+        Ok(Status::Ok)
+    }
+}
+
+fn parse_durability(durability: &str) -> Result<Durability> {
+    match durability.to_lowercase().as_str() {
+        "none" => Ok(Durability::None),
+        "eventual" => Ok(Durability::Eventual),
+        "immediate" => Ok(Durability::Immediate),
+        _ => Err(anyhow!("unknown redb durability {}", durability)),
+    }
+}
+
+impl DB for RedbDB {
+    fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn insert(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status(self.insert_record(table, key, values))
+    }
+
+    fn read(&self, table: &str, key: &str, fields: Option<&[&str]>, result: &mut Record) -> Status {
+        error_status(self.read_record(table, key, fields, result))
+    }
+
+    fn update(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status(self.update_record(table, key, values))
+    }
+
+    fn delete(&self, table: &str, key: &str) -> Status {
+        error_status(self.delete_record(table, key))
+    }
+
+    fn scan(
+        &self,
+        table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<Record>,
+    ) -> Status {
+        error_status(self.scan_records(table, start_key, record_count, result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{check_crud, values};
+    use redb::backends::InMemoryBackend;
+
+    #[test]
+    fn test_operations() {
+        let db = RedbDB {
+            db: Database::builder()
+                .create_with_backend(InMemoryBackend::new())
+                .unwrap(),
+            durability: Durability::None,
+        };
+        check_crud(&db, "usertable");
+        assert_eq!(db.update("usertable", "a", &values(b"1")), Status::NotFound);
+        assert_eq!(db.delete("usertable", "a"), Status::NotFound);
+    }
+
+    #[test]
+    fn test_parse_durability() {
+        assert!(matches!(parse_durability("none"), Ok(Durability::None)));
+        assert!(matches!(
+            parse_durability("Immediate"),
+            Ok(Durability::Immediate)
+        ));
+        assert!(parse_durability("always").is_err());
+    }
+}
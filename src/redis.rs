@@ -0,0 +1,329 @@
+use crate::db::{error_status_with, Status, DB};
+use crate::properties::Properties;
+
+use anyhow::Result;
+use redis::{
+    Client, Cmd, Connection, ConnectionAddr, ConnectionInfo, RedisConnectionInfo, RedisError,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type Record = HashMap<String, Vec<u8>>;
+
+// Sorted set of all keys, scored by `hash`, that scans walk.
+const INDEX: &str = "_indices";
+
+// Upstream YCSB scores keys by Java's `String.hashCode()`, so scans return
+// the same records for the same start key.
+fn hash(key: &str) -> f64 {
+    key.encode_utf16()
+        .fold(0i32, |hash, c| hash.wrapping_mul(31).wrapping_add(c as i32)) as f64
+}
+
+fn classify(err: &anyhow::Error) -> Status {
+    match err.downcast_ref::<RedisError>() {
+        Some(err) if err.is_io_error() || err.is_connection_dropped() => Status::ServiceUnavailable,
+        _ => Status::Error,
+    }
+}
+
+// Every record is a hash and its key is added to the `INDEX` sorted set.
+pub struct Redis {
+    conn: RefCell<Connection>,
+}
+
+impl Redis {
+    pub fn new(props: Arc<Properties>) -> Result<Self> {
+        let redis_props = &props.redis;
+        let info = ConnectionInfo {
+            addr: ConnectionAddr::Tcp(redis_props.host.clone(), redis_props.port),
+            redis: RedisConnectionInfo {
+                password: redis_props.password.clone(),
+                ..Default::default()
+            },
+        };
+        let conn = Client::open(info)?.get_connection()?;
+        Ok(Redis {
+            conn: RefCell::new(conn),
+        })
+    }
+
+    fn hset(key: &str, values: &HashMap<&str, Vec<u8>>) -> Cmd {
+        let mut cmd = redis::cmd("HSET");
+        cmd.arg(key);
+        for (field, value) in values {
+            cmd.arg(field).arg(value);
+        }
+        cmd
+    }
+
+    fn insert_record(&self, key: &str, values: &HashMap<&str, Vec<u8>>) -> Result<Status> {
+        redis::pipe()
+            .add_command(Self::hset(key, values))
+            .ignore()
+            .zadd(INDEX, key, hash(key))
+            .ignore()
+            .query::<()>(&mut *self.conn.borrow_mut())?;
+        Ok(Status::Ok)
+    }
+
+    fn read_record(
+        &self,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut Record,
+    ) -> Result<Status> {
+        let mut conn = self.conn.borrow_mut();
+        let record: Record = match fields {
+            None => redis::cmd("HGETALL").arg(key).query(&mut *conn)?,
+            Some(fields) => {
+                let values: Vec<Option<Vec<u8>>> =
+                    redis::cmd("HMGET").arg(key).arg(fields).query(&mut *conn)?;
+                fields
+                    .iter()
+                    .zip(values)
+                    .filter_map(|(field, value)| Some((field.to_string(), value?)))
+                    .collect()
+            }
+        };
+        if record.is_empty() {
+            return Ok(Status::NotFound);
+        }
+        result.extend(record);
+        Ok(Status::Ok)
+    }
+
+    // Like upstream YCSB, updating a missing key creates it.
+    fn update_record(&self, key: &str, values: &HashMap<&str, Vec<u8>>) -> Result<Status> {
+        Self::hset(key, values).query::<()>(&mut *self.conn.borrow_mut())?;
+        Ok(Status::Ok)
+    }
+
+    fn delete_record(&self, key: &str) -> Result<Status> {
+        let (deleted,): (i64,) = redis::pipe()
+            .del(key)
+            .zrem(INDEX, key)
+            .ignore()
+            .query(&mut *self.conn.borrow_mut())?;
+        Ok(if deleted > 0 {
+            Status::Ok
+        } else {
+            Status::NotFound
+        })
+    }
+
+    fn scan_records(
+        &self,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<Record>,
+    ) -> Result<Status> {
+        let mut conn = self.conn.borrow_mut();
+        let keys: Vec<String> = redis::cmd("ZRANGEBYSCORE")
+            .arg(INDEX)
+            .arg(hash(start_key))
+            .arg("+inf")
+            .arg("LIMIT")
+            .arg(0)
+            .arg(record_count)
+            .query(&mut *conn)?;
+        let mut pipe = redis::pipe();
+        for key in &keys {
+            pipe.cmd("HGETALL").arg(key);
+        }
+        let records: Vec<Record> = pipe.query(&mut *conn)?;
+        result.extend(records);
+        Ok(Status::Ok)
+    }
+}
+
+impl DB for Redis {
+    fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn insert(&self, _table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status_with(self.insert_record(key, values), classify)
+    }
+
+    fn read(
+        &self,
+        _table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut Record,
+    ) -> Status {
+        error_status_with(self.read_record(key, fields, result), classify)
+    }
+
+    fn update(&self, _table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status_with(self.update_record(key, values), classify)
+    }
+
+    fn delete(&self, _table: &str, key: &str) -> Status {
+        error_status_with(self.delete_record(key), classify)
+    }
+
+    fn scan(
+        &self,
+        _table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<Record>,
+    ) -> Status {
+        error_status_with(self.scan_records(start_key, record_count, result), classify)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{check_crud, values};
+    use std::collections::BTreeMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    #[derive(Default)]
+    struct FakeRedis {
+        hashes: HashMap<Vec<u8>, BTreeMap<Vec<u8>, Vec<u8>>>,
+        // member -> score
+        index: HashMap<Vec<u8>, f64>,
+    }
+
+    enum Reply {
+        Integer(usize),
+        Array(Vec<Vec<u8>>),
+        // an array that may contain nil
+        Values(Vec<Option<Vec<u8>>>),
+        Error,
+    }
+
+    impl FakeRedis {
+        fn execute(&mut self, args: &[Vec<u8>]) -> Reply {
+            let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+            match name.as_str() {
+                "HSET" => {
+                    let hash = self.hashes.entry(args[1].clone()).or_default();
+                    let mut added = 0;
+                    for pair in args[2..].chunks(2) {
+                        if hash.insert(pair[0].clone(), pair[1].clone()).is_none() {
+                            added += 1;
+                        }
+                    }
+                    Reply::Integer(added)
+                }
+                "HMGET" => {
+                    let hash = self.hashes.get(&args[1]);
+                    let values = args[2..]
+                        .iter()
+                        .map(|field| hash.and_then(|hash| hash.get(field)).cloned());
+                    Reply::Values(values.collect())
+                }
+                "HGETALL" => {
+                    let hash = self.hashes.get(&args[1]).cloned().unwrap_or_default();
+                    Reply::Array(hash.into_iter().flat_map(|(k, v)| [k, v]).collect())
+                }
+                "DEL" => Reply::Integer(self.hashes.remove(&args[1]).is_some() as usize),
+                "ZADD" => {
+                    let score = String::from_utf8_lossy(&args[2]).parse().unwrap();
+                    Reply::Integer(self.index.insert(args[3].clone(), score).is_none() as usize)
+                }
+                "ZREM" => Reply::Integer(self.index.remove(&args[2]).is_some() as usize),
+                "ZRANGEBYSCORE" => {
+                    let min: f64 = String::from_utf8_lossy(&args[2]).parse().unwrap();
+                    let count: usize = String::from_utf8_lossy(&args[6]).parse().unwrap();
+                    let mut members: Vec<(f64, Vec<u8>)> = self
+                        .index
+                        .iter()
+                        .filter(|(_, score)| **score >= min)
+                        .map(|(member, score)| (*score, member.clone()))
+                        .collect();
+                    members.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                    Reply::Array(members.into_iter().take(count).map(|(_, m)| m).collect())
+                }
+                _ => Reply::Error,
+            }
+        }
+    }
+
+    fn read_command(reader: &mut impl BufRead) -> Option<Vec<Vec<u8>>> {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let count: usize = line.trim_end()[1..].parse().ok()?;
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            line.clear();
+            reader.read_line(&mut line).ok()?;
+            let len: usize = line.trim_end()[1..].parse().ok()?;
+            let mut arg = vec![0; len + 2];
+            reader.read_exact(&mut arg).ok()?;
+            arg.truncate(len);
+            args.push(arg);
+        }
+        Some(args)
+    }
+
+    // Serves a single client with the handful of commands the backend uses.
+    fn serve(stream: TcpStream) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut redis = FakeRedis::default();
+        while let Some(args) = read_command(&mut reader) {
+            let mut out = Vec::new();
+            match redis.execute(&args) {
+                Reply::Integer(n) => out.extend(format!(":{}\r\n", n).as_bytes()),
+                Reply::Array(items) => {
+                    out.extend(format!("*{}\r\n", items.len()).as_bytes());
+                    for item in items {
+                        out.extend(format!("${}\r\n", item.len()).as_bytes());
+                        out.extend(item);
+                        out.extend(b"\r\n");
+                    }
+                }
+                Reply::Values(values) => {
+                    out.extend(format!("*{}\r\n", values.len()).as_bytes());
+                    for value in values {
+                        match value {
+                            Some(value) => {
+                                out.extend(format!("${}\r\n", value.len()).as_bytes());
+                                out.extend(value);
+                                out.extend(b"\r\n");
+                            }
+                            None => out.extend(b"$-1\r\n"),
+                        }
+                    }
+                }
+                Reply::Error => out.extend(b"-ERR unknown command\r\n"),
+            }
+            writer.write_all(&out).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_hash() {
+        // Matches Java's "user1".hashCode().
+        assert_eq!(hash("user1"), 111578566.0);
+        assert_eq!(hash(""), 0.0);
+    }
+
+    #[test]
+    fn test_operations() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || serve(listener.accept().unwrap().0));
+        let mut props: Properties = toml::from_str("operationcount = 1").unwrap();
+        props.redis.host = "127.0.0.1".to_string();
+        props.redis.port = port;
+        let db = Redis::new(Arc::new(props)).unwrap();
+        check_crud(&db, "usertable");
+        assert_eq!(db.delete("usertable", "a"), Status::NotFound);
+        // Updating a missing key creates it.
+        assert_eq!(db.update("usertable", "a", &values(b"1")), Status::Ok);
+        let mut result = HashMap::new();
+        assert_eq!(db.read("usertable", "a", None, &mut result), Status::Ok);
+        assert_eq!(result["field0"], b"1");
+    }
+}
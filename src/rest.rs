@@ -0,0 +1,360 @@
+use crate::db::{error_status_with, field_selected, Status, DB};
+use crate::properties::{Properties, RestEndpoint};
+
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use ureq::{Agent, AgentBuilder};
+
+fn classify(err: &anyhow::Error) -> Status {
+    match err.downcast_ref::<ureq::Error>() {
+        Some(ureq::Error::Transport(_)) => Status::ServiceUnavailable,
+        _ => Status::Error,
+    }
+}
+
+// Same mapping as upstream YCSB's REST binding.
+fn status_for_code(code: u16) -> Status {
+    match code {
+        200..=299 => Status::Ok,
+        400 => Status::BadRequest,
+        403 => Status::Forbidden,
+        404 => Status::NotFound,
+        501 => Status::NotImplemented,
+        503 => Status::ServiceUnavailable,
+        _ => Status::Error,
+    }
+}
+
+struct Endpoint {
+    method: String,
+    url: String,
+}
+
+impl Endpoint {
+    fn new(endpoint: &RestEndpoint, default_method: &str) -> Self {
+        Endpoint {
+            method: endpoint
+                .method
+                .clone()
+                .unwrap_or_else(|| default_method.to_string()),
+            url: endpoint.url.clone(),
+        }
+    }
+}
+
+// Every operation is one HTTP request to a URL template in which `{table}`
+// and `{key}` are substituted. Records are JSON objects that map field
+// names to base64 encoded values.
+pub struct Rest {
+    agent: Agent,
+    insert: Endpoint,
+    read: Endpoint,
+    update: Endpoint,
+    delete: Endpoint,
+}
+
+impl Rest {
+    pub fn new(props: Arc<Properties>) -> Result<Self> {
+        let rest_props = &props.rest;
+        let mut builder = AgentBuilder::new();
+        if !rest_props.keepalive {
+            builder = builder.max_idle_connections(0);
+        }
+        if rest_props.connect_timeout > 0 {
+            builder = builder.timeout_connect(Duration::from_millis(rest_props.connect_timeout));
+        }
+        if rest_props.timeout > 0 {
+            builder = builder.timeout(Duration::from_millis(rest_props.timeout));
+        }
+        Ok(Rest {
+            agent: builder.build(),
+            insert: Endpoint::new(&rest_props.insert, "POST"),
+            read: Endpoint::new(&rest_props.read, "GET"),
+            update: Endpoint::new(&rest_props.update, "PUT"),
+            delete: Endpoint::new(&rest_props.delete, "DELETE"),
+        })
+    }
+
+    // Returns the status code and body of the response.
+    fn send(
+        &self,
+        endpoint: &Endpoint,
+        table: &str,
+        key: &str,
+        body: Option<&str>,
+    ) -> Result<(u16, String)> {
+        let url = endpoint.url.replace("{table}", table).replace("{key}", key);
+        let request = self.agent.request(&endpoint.method, &url);
+        let result = match body {
+            Some(body) => request
+                .set("Content-Type", "application/json")
+                .send_string(body),
+            None => request.call(),
+        };
+        // The body is always read to the end so that the connection can be
+        // reused.
+        match result {
+            Ok(response) => Ok((response.status(), response.into_string()?)),
+            Err(ureq::Error::Status(code, response)) => Ok((code, response.into_string()?)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn encode(values: &HashMap<&str, Vec<u8>>) -> Result<String> {
+        let values: HashMap<&str, String> = values
+            .iter()
+            .map(|(field, value)| (*field, BASE64.encode(value)))
+            .collect();
+        Ok(serde_json::to_string(&values)?)
+    }
+
+    fn write_record(
+        &self,
+        endpoint: &Endpoint,
+        table: &str,
+        key: &str,
+        values: &HashMap<&str, Vec<u8>>,
+    ) -> Result<Status> {
+        let body = Self::encode(values)?;
+        let (code, _) = self.send(endpoint, table, key, Some(&body))?;
+        Ok(status_for_code(code))
+    }
+
+    fn read_record(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut HashMap<String, Vec<u8>>,
+    ) -> Result<Status> {
+        let (code, body) = self.send(&self.read, table, key, None)?;
+        let status = status_for_code(code);
+        if status != Status::Ok {
+            return Ok(status);
+        }
+        let values: HashMap<String, String> = serde_json::from_str(&body)?;
+        // The endpoint returns whole records.
+        for (field, value) in values {
+            if !field_selected(fields, &field) {
+                continue;
+            }
+            let value = BASE64
+                .decode(value)
+                .map_err(|err| anyhow!("field {}: {}", field, err))?;
+            result.insert(field, value);
+        }
+        Ok(Status::Ok)
+    }
+
+    fn delete_record(&self, table: &str, key: &str) -> Result<Status> {
+        let (code, _) = self.send(&self.delete, table, key, None)?;
+        Ok(status_for_code(code))
+    }
+}
+
+impl DB for Rest {
+    fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn insert(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status_with(
+            self.write_record(&self.insert, table, key, values),
+            classify,
+        )
+    }
+
+    fn read(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut HashMap<String, Vec<u8>>,
+    ) -> Status {
+        error_status_with(self.read_record(table, key, fields, result), classify)
+    }
+
+    fn update(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status_with(
+            self.write_record(&self.update, table, key, values),
+            classify,
+        )
+    }
+
+    fn delete(&self, table: &str, key: &str) -> Status {
+        error_status_with(self.delete_record(table, key), classify)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{check_crud, values};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    // Returns the method, path and body of the next request on the
+    // connection.
+    fn read_request(reader: &mut impl BufRead) -> Option<(String, String, String)> {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let mut parts = line.split_whitespace();
+        let method = parts.next()?.to_string();
+        let path = parts.next()?.to_string();
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).ok()?;
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().ok()?;
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).ok()?;
+        Some((method, path, String::from_utf8(body).ok()?))
+    }
+
+    // A key-value store where POST creates, PUT replaces an existing record,
+    // GET reads and DELETE removes the record at a path.
+    fn serve(stream: TcpStream, records: &Mutex<HashMap<String, String>>) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        while let Some((method, path, body)) = read_request(&mut reader) {
+            let mut records = records.lock().unwrap();
+            let (code, body) = match method.as_str() {
+                "POST" => match records.contains_key(&path) {
+                    true => (409, String::new()),
+                    false => {
+                        records.insert(path, body);
+                        (201, String::new())
+                    }
+                },
+                "PUT" => match records.get_mut(&path) {
+                    Some(record) => {
+                        *record = body;
+                        (204, String::new())
+                    }
+                    None => (404, String::new()),
+                },
+                "GET" => match records.get(&path) {
+                    Some(record) => (200, record.clone()),
+                    None => (404, String::new()),
+                },
+                "DELETE" => match records.remove(&path) {
+                    Some(_) => (204, String::new()),
+                    None => (404, String::new()),
+                },
+                _ => (501, String::new()),
+            };
+            write!(
+                writer,
+                "HTTP/1.1 {} Status\r\nContent-Length: {}\r\n\r\n{}",
+                code,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    }
+
+    // Returns the port of the server and the number of accepted connections.
+    fn start_server() -> (u16, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        std::thread::spawn(move || {
+            let records = Arc::new(Mutex::new(HashMap::new()));
+            for stream in listener.incoming() {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let records = records.clone();
+                std::thread::spawn(move || serve(stream.unwrap(), &records));
+            }
+        });
+        (port, connections)
+    }
+
+    fn connect(port: u16, keepalive: bool) -> Rest {
+        let url = format!("http://127.0.0.1:{}/{{table}}/{{key}}", port);
+        let mut props: Properties = toml::from_str("operationcount = 1").unwrap();
+        for endpoint in [
+            &mut props.rest.insert,
+            &mut props.rest.read,
+            &mut props.rest.update,
+            &mut props.rest.delete,
+        ] {
+            endpoint.url = url.clone();
+        }
+        props.rest.keepalive = keepalive;
+        Rest::new(Arc::new(props)).unwrap()
+    }
+
+    #[test]
+    fn test_operations() {
+        let (port, connections) = start_server();
+        let db = connect(port, true);
+        let table = "usertable";
+        check_crud(&db, table);
+        // Values are not valid UTF-8, so they must round-trip through base64.
+        assert_eq!(db.insert(table, "a", &values(b"\x00\xff")), Status::Ok);
+        assert_eq!(db.insert(table, "a", &values(b"1")), Status::Error);
+        let mut result = HashMap::new();
+        assert_eq!(db.read(table, "a", None, &mut result), Status::Ok);
+        assert_eq!(result["field0"], b"\x00\xff");
+        assert_eq!(db.update(table, "b", &values(b"2")), Status::NotFound);
+        assert_eq!(db.delete(table, "a"), Status::Ok);
+        assert_eq!(db.delete(table, "a"), Status::NotFound);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_endpoint_defaults() {
+        let props: Properties = toml::from_str(
+            "operationcount = 1\n[rest.read]\nurl = \"https://example.com/{key}\"\n\
+             [rest.delete]\nmethod = \"POST\"",
+        )
+        .unwrap();
+        let db = Rest::new(Arc::new(props)).unwrap();
+        assert_eq!(db.read.method, "GET");
+        assert_eq!(db.read.url, "https://example.com/{key}");
+        assert_eq!(db.insert.method, "POST");
+        assert_eq!(db.delete.method, "POST");
+        assert_eq!(db.delete.url, "http://localhost:8080/{table}/{key}");
+    }
+
+    #[test]
+    fn test_without_keepalive() {
+        let (port, connections) = start_server();
+        let db = connect(port, false);
+        assert_eq!(db.insert("usertable", "a", &values(b"1")), Status::Ok);
+        assert_eq!(db.delete("usertable", "a"), Status::Ok);
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_connection_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let db = connect(port, true);
+        let mut result = HashMap::new();
+        assert_eq!(
+            db.read("usertable", "a", None, &mut result),
+            Status::ServiceUnavailable
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use crate::db::{error_status, field_selected, Status, DB};
+use crate::properties::Properties;
+use crate::utils::fnvhash64_bytes;
+
+use anyhow::{anyhow, Result};
+use rocksdb::{
+    BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor, DBCompressionType, Direction,
+    IteratorMode, Options, WriteOptions,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const LOCKS: usize = 64;
+
+type Record = HashMap<String, Vec<u8>>;
+
+// All tables live in the configured column family.
+pub struct RocksDB {
+    db: rocksdb::DB,
+    column_family: String,
+    write_options: WriteOptions,
+    // An update reads, modifies and writes back the whole record, so all
+    // writes of a key are serialized by the lock its hash picks.
+    locks: Vec<Mutex<()>>,
+}
+
+impl RocksDB {
+    pub fn new(props: Arc<Properties>) -> Result<Self> {
+        let rocksdb_props = &props.rocksdb;
+        let column_family = rocksdb_props
+            .column_family
+            .clone()
+            .unwrap_or_else(|| props.table.clone());
+
+        let cache = Cache::new_lru_cache(rocksdb_props.block_cache_size);
+        let mut block_options = BlockBasedOptions::default();
+        block_options.set_block_cache(&cache);
+        let mut cf_options = Options::default();
+        cf_options.set_block_based_table_factory(&block_options);
+        cf_options.set_compression_type(parse_compression(&rocksdb_props.compression)?);
+
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let db = rocksdb::DB::open_cf_descriptors(
+            &options,
+            &rocksdb_props.path,
+            vec![ColumnFamilyDescriptor::new(&column_family, cf_options)],
+        )?;
+
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(rocksdb_props.sync);
+        write_options.disable_wal(rocksdb_props.disable_wal);
+
+        let mut locks = Vec::with_capacity(LOCKS);
+        locks.resize_with(LOCKS, Default::default);
+
+        Ok(RocksDB {
+            db,
+            column_family,
+            write_options,
+            locks,
+        })
+    }
+
+    fn lock(&self, key: &str) -> MutexGuard<'_, ()> {
+        let idx = fnvhash64_bytes(key.as_bytes()) as usize % LOCKS;
+        self.locks[idx].lock().unwrap()
+    }
+
+    fn cf(&self) -> Result<&ColumnFamily> {
+        self.db
+            .cf_handle(&self.column_family)
+            .ok_or_else(|| anyhow!("column family {} not found", self.column_family))
+    }
+
+    fn insert_record(&self, key: &str, values: &HashMap<&str, Vec<u8>>) -> Result<Status> {
+        let value = bincode::serialize(values)?;
+        let _lock = self.lock(key);
+        self.db
+            .put_cf_opt(self.cf()?, key, value, &self.write_options)?;
+        Ok(Status::Ok)
+    }
+
+    fn read_record(
+        &self,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut Record,
+    ) -> Result<Status> {
+        match self.db.get_pinned_cf(self.cf()?, key)? {
+            Some(value) => {
+                let record = bincode::deserialize::<Record>(&value)?;
+                result.extend(
+                    record
+                        .into_iter()
+                        .filter(|(field, _)| field_selected(fields, field)),
+                );
+                Ok(Status::Ok)
+            }
+            None => Ok(Status::NotFound),
+        }
+    }
+
+    fn update_record(&self, key: &str, values: &HashMap<&str, Vec<u8>>) -> Result<Status> {
+        let _lock = self.lock(key);
+        let mut record = Record::new();
+        if self.read_record(key, None, &mut record)? == Status::NotFound {
+            return Ok(Status::NotFound);
+        }
+        for (field, value) in values {
+            record.insert(field.to_string(), value.clone());
+        }
+        let value = bincode::serialize(&record)?;
+        self.db
+            .put_cf_opt(self.cf()?, key, value, &self.write_options)?;
+        Ok(Status::Ok)
+    }
+
+    fn delete_record(&self, key: &str) -> Result<Status> {
+        let _lock = self.lock(key);
+        self.db
+            .delete_cf_opt(self.cf()?, key, &self.write_options)?;
+        Ok(Status::Ok)
+    }
+
+    fn scan_records(
+        &self,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<Record>,
+    ) -> Result<Status> {
+        let mode = IteratorMode::From(start_key.as_bytes(), Direction::Forward);
+        for entry in self.db.iterator_cf(self.cf()?, mode).take(record_count) {
+            let (_, value) = entry?;
+            result.push(bincode::deserialize(&value)?);
+        }
+        Ok(Status::Ok)
+    }
+}
+
+// Only the codecs enabled in Cargo.toml are built into RocksDB.
+fn parse_compression(compression: &str) -> Result<DBCompressionType> {
+    match compression.to_lowercase().as_str() {
+        "none" => Ok(DBCompressionType::None),
+        "lz4" => Ok(DBCompressionType::Lz4),
+        "zstd" => Ok(DBCompressionType::Zstd),
+        _ => Err(anyhow!("unknown rocksdb compression {}", compression)),
+    }
+}
+
+impl DB for RocksDB {
+    fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn cleanup(&self) -> Result<()> {
+        self.db.flush_cf(self.cf()?)?;
+        Ok(())
+    }
+
+    fn insert(&self, _table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status(self.insert_record(key, values))
+    }
+
+    fn read(
+        &self,
+        _table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut Record,
+    ) -> Status {
+        error_status(self.read_record(key, fields, result))
+    }
+
+    fn update(&self, _table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status(self.update_record(key, values))
+    }
+
+    // RocksDB deletes are blind writes, so deleting a missing key is not
+    // reported as NOT_FOUND.
+    fn delete(&self, _table: &str, key: &str) -> Status {
+        error_status(self.delete_record(key))
+    }
+
+    fn scan(
+        &self,
+        _table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<Record>,
+    ) -> Status {
+        error_status(self.scan_records(start_key, record_count, result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{check_crud, values};
+
+    #[test]
+    fn test_operations() {
+        let dir = std::env::temp_dir().join(format!("ycsb-rocksdb-{}", std::process::id()));
+        let mut props: Properties = toml::from_str("operationcount = 1").unwrap();
+        props.rocksdb.path = dir.to_str().unwrap().to_string();
+        let db = RocksDB::new(Arc::new(props)).unwrap();
+        check_crud(&db, "usertable");
+        assert_eq!(db.update("usertable", "a", &values(b"1")), Status::NotFound);
+        drop(db);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_updates_of_one_key() {
+        let dir = std::env::temp_dir().join(format!("ycsb-rocksdb-rmw-{}", std::process::id()));
+        let mut props: Properties = toml::from_str("operationcount = 1").unwrap();
+        props.rocksdb.path = dir.to_str().unwrap().to_string();
+        let db = RocksDB::new(Arc::new(props)).unwrap();
+        assert_eq!(db.insert("usertable", "a", &values(b"0")), Status::Ok);
+        std::thread::scope(|s| {
+            for field in ["field0", "field1"] {
+                let db = &db;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        let mut values = HashMap::new();
+                        values.insert(field, i.to_string().into_bytes());
+                        assert_eq!(db.update("usertable", "a", &values), Status::Ok);
+                    }
+                });
+            }
+        });
+        let mut result = HashMap::new();
+        assert_eq!(db.read("usertable", "a", None, &mut result), Status::Ok);
+        assert_eq!(result["field0"], b"999");
+        assert_eq!(result["field1"], b"999");
+        drop(db);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_compression() {
+        assert!(matches!(
+            parse_compression("none"),
+            Ok(DBCompressionType::None)
+        ));
+        assert!(matches!(
+            parse_compression("ZSTD"),
+            Ok(DBCompressionType::Zstd)
+        ));
+        assert!(parse_compression("snappy").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::db::{error_status, field_selected, Status, DB};
+use crate::properties::Properties;
+
+use anyhow::Result;
+use sled::{Config, Db, Tree};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type Record = HashMap<String, Vec<u8>>;
+
+// Every table is a sled tree.
+pub struct SledDB {
+    db: Db,
+}
+
+impl SledDB {
+    pub fn new(props: Arc<Properties>) -> Result<Self> {
+        let sled_props = &props.sled;
+        let flush_every_ms = match sled_props.flush_every_ms {
+            0 => None,
+            ms => Some(ms),
+        };
+        let db = Config::new()
+            .path(&sled_props.path)
+            .cache_capacity(sled_props.cache_capacity)
+            .flush_every_ms(flush_every_ms)
+            .open()?;
+        Ok(SledDB { db })
+    }
+
+    fn tree(&self, table: &str) -> Result<Tree> {
+        Ok(self.db.open_tree(table)?)
+    }
+
+    fn insert_record(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Result<()> {
+        let value = bincode::serialize(values)?;
+        self.tree(table)?.insert(key, value)?;
+        Ok(())
+    }
+
+    fn read_record(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut Record,
+    ) -> Result<Status> {
+        match self.tree(table)?.get(key)? {
+            Some(value) => {
+                let record = bincode::deserialize::<Record>(&value)?;
+                result.extend(
+                    record
+                        .into_iter()
+                        .filter(|(field, _)| field_selected(fields, field)),
+                );
+                Ok(Status::Ok)
+            }
+            None => Ok(Status::NotFound),
+        }
+    }
+
+    fn update_record(
+        &self,
+        table: &str,
+        key: &str,
+        values: &HashMap<&str, Vec<u8>>,
+    ) -> Result<Status> {
+        let mut found = true;
+        let mut error = None;
+        // The closure may run more than once if another thread updates the
+        // same key concurrently.
+        self.tree(table)?.fetch_and_update(key, |old| {
+            let old = match old {
+                Some(old) => old,
+                None => {
+                    found = false;
+                    return None;
+                }
+            };
+            found = true;
+            let mut record = match bincode::deserialize::<Record>(old) {
+                Ok(record) => record,
+                Err(err) => {
+                    error = Some(err);
+                    return Some(old.to_vec());
+                }
+            };
+            for (field, value) in values {
+                record.insert(field.to_string(), value.clone());
+            }
+            bincode::serialize(&record).ok()
+        })?;
+        if let Some(err) = error {
+            return Err(err.into());
+        }
+        Ok(if found { Status::Ok } else { Status::NotFound })
+    }
+
+    fn delete_record(&self, table: &str, key: &str) -> Result<Status> {
+        match self.tree(table)?.remove(key)? {
+            Some(_) => Ok(Status::Ok),
+            None => Ok(Status::NotFound),
+        }
+    }
+
+    fn scan_records(
+        &self,
+        table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<Record>,
+    ) -> Result<()> {
+        for entry in self.tree(table)?.range(start_key..).take(record_count) {
+            let (_, value) = entry?;
+            result.push(bincode::deserialize(&value)?);
+        }
+        Ok(())
+    }
+}
+
+impl DB for SledDB {
+    fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn cleanup(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn insert(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status(self.insert_record(table, key, values).map(|_| Status::Ok))
+    }
+
+    fn read(&self, table: &str, key: &str, fields: Option<&[&str]>, result: &mut Record) -> Status {
+        error_status(self.read_record(table, key, fields, result))
+    }
+
+    fn update(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status(self.update_record(table, key, values))
+    }
+
+    fn delete(&self, table: &str, key: &str) -> Status {
+        error_status(self.delete_record(table, key))
+    }
+
+    fn scan(
+        &self,
+        table: &str,
+        start_key: &str,
+        record_count: usize,
+        result: &mut Vec<Record>,
+    ) -> Status {
+        error_status(
+            self.scan_records(table, start_key, record_count, result)
+                .map(|_| Status::Ok),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{check_crud, values};
+
+    #[test]
+    fn test_operations() {
+        let db = SledDB {
+            db: Config::new().temporary(true).open().unwrap(),
+        };
+        check_crud(&db, "usertable");
+        assert_eq!(db.update("usertable", "a", &values(b"1")), Status::NotFound);
+        assert_eq!(db.delete("usertable", "a"), Status::NotFound);
+    }
+}
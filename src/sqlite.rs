@@ -1,73 +1,504 @@
-use crate::db::DB;
+use crate::db::{error_status_with, Status, DB};
+use crate::properties::Properties;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use sql_builder::SqlBuilder;
-use sqlite::{Connection, OpenFlags, State};
+use sqlite::{Connection, OpenFlags, State, Statement};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::os::raw::c_int;
+use std::sync::{Arc, Once, OnceLock};
 
 const PRIMARY_KEY: &str = "y_id";
 
+// Every worker thread opens its own connection and calls `init`, but the
+// table must only be dropped once per process.
+static DROP_TABLE: Once = Once::new();
+
+// The effective pragmas are printed once, by the first connection.
+static PRINT_PRAGMAS: Once = Once::new();
+
+// Applied in this order: the page size must be set before the database is
+// created and before switching to WAL mode.
+const PRAGMAS: &[&str] = &[
+    "page_size",
+    "journal_mode",
+    "synchronous",
+    "cache_size",
+    "mmap_size",
+];
+
+// URI filenames must be enabled before SQLite is initialized, i.e. before the
+// first connection is opened. Holds the result code for every later thread.
+static ENABLE_URI: OnceLock<c_int> = OnceLock::new();
+
+fn classify(err: &anyhow::Error) -> Status {
+    let code = err
+        .downcast_ref::<sqlite::Error>()
+        .and_then(|err| err.code)
+        .map(|code| code as i32 & 0xff);
+    match code {
+        Some(sqlite3_sys::SQLITE_BUSY) => Status::Busy,
+        Some(sqlite3_sys::SQLITE_LOCKED) => Status::Locked,
+        Some(sqlite3_sys::SQLITE_CONSTRAINT) => Status::Constraint,
+        _ => Status::Error,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Insert,
+    Read,
+}
+
+struct CachedStatement {
+    op: Operation,
+    table: String,
+    fields: Vec<String>,
+    stmt: Statement<'static>,
+}
+
 pub struct SQLite {
+    // Statements borrow the connection, so they must be dropped before it.
+    // Fields are dropped in declaration order.
+    //
+    // There is only a handful of distinct statements per connection, so a
+    // linear scan is cheaper than hashing an owned key on every lookup.
+    statements: RefCell<Vec<CachedStatement>>,
+    // batched inserts in the currently open transaction
+    pending_inserts: Cell<usize>,
     conn: Connection,
+    props: Arc<Properties>,
 }
 
 impl SQLite {
-    pub fn new() -> Result<Self> {
-        let flags = OpenFlags::new().set_read_write().set_no_mutex();
-        let mut conn = Connection::open_with_flags("test.db", flags)?;
-        conn.set_busy_timeout(5000)?;
-        Ok(SQLite { conn })
+    pub fn new(props: Arc<Properties>) -> Result<Self> {
+        let sqlite_props = &props.sqlite;
+        let mut flags = OpenFlags::new().set_read_write().set_no_mutex();
+        if sqlite_props.create {
+            flags = flags.set_create();
+        }
+        let path = if sqlite_props.shared_cache {
+            let code = *ENABLE_URI.get_or_init(|| unsafe {
+                sqlite3_sys::sqlite3_config(sqlite3_sys::SQLITE_CONFIG_URI, 1)
+            });
+            if code != sqlite3_sys::SQLITE_OK {
+                bail!(
+                    "enabling URI filenames for sharedcache failed (code {}), SQLite was already initialized",
+                    code
+                );
+            }
+            format!("file:{}?cache=shared", sqlite_props.path)
+        } else {
+            sqlite_props.path.clone()
+        };
+        let mut conn = Connection::open_with_flags(path, flags)?;
+        conn.set_busy_timeout(sqlite_props.busy_timeout)?;
+        Ok(SQLite {
+            statements: RefCell::new(Vec::new()),
+            pending_inserts: Cell::new(0),
+            conn,
+            props,
+        })
     }
-}
 
-impl DB for SQLite {
-    fn init(&self) -> Result<()> {
-        Ok(())
+    fn with_statement<T>(
+        &self,
+        op: Operation,
+        table: &str,
+        fields: &[&str],
+        sql: impl FnOnce() -> Result<String>,
+        f: impl FnOnce(&mut Statement) -> Result<T>,
+    ) -> Result<T> {
+        let mut statements = self.statements.borrow_mut();
+        let idx = match statements
+            .iter()
+            .position(|cached| cached.op == op && cached.table == table && cached.fields == fields)
+        {
+            Some(idx) => idx,
+            None => {
+                let stmt = self.conn.prepare(sql()?)?;
+                // SAFETY: the statement is finalized before the connection is
+                // closed, see the field order of `SQLite`.
+                let stmt = unsafe { std::mem::transmute::<Statement, Statement<'static>>(stmt) };
+                statements.push(CachedStatement {
+                    op,
+                    table: table.to_string(),
+                    fields: fields.iter().map(|field| field.to_string()).collect(),
+                    stmt,
+                });
+                statements.len() - 1
+            }
+        };
+        let stmt = &mut statements[idx].stmt;
+        let result = f(stmt);
+        // Release any locks held by the statement before the next operation.
+        // An error from `f` takes precedence, resetting after a failed step
+        // reports the same error again.
+        let reset = stmt.reset();
+        let value = result?;
+        reset?;
+        Ok(value)
     }
 
-    fn insert(&self, table: &str, key: &str, values: &HashMap<&str, String>) -> Result<()> {
-        // TODO: cache prepared statement
-        let mut sql = SqlBuilder::insert_into(table);
-        let mut vals: Vec<String> = Vec::new();
-        sql.field(PRIMARY_KEY);
-        vals.push(format!(":{}", PRIMARY_KEY));
-        for key in values.keys() {
-            sql.field(key);
-            let marker = format!(":{}", key);
-            vals.push(marker);
+    fn apply_pragmas(&self) -> Result<()> {
+        let pragmas = &self.props.sqlite.pragmas;
+        let values = [
+            pragmas.page_size.map(|v| v.to_string()),
+            pragmas.journal_mode.clone(),
+            pragmas.synchronous.clone(),
+            pragmas.cache_size.map(|v| v.to_string()),
+            pragmas.mmap_size.map(|v| v.to_string()),
+        ];
+        for (name, value) in PRAGMAS.iter().zip(values) {
+            if let Some(value) = value {
+                self.conn.execute(format!("PRAGMA {} = {}", name, value))?;
+            }
         }
-        sql.values(&vals);
-        let sql = sql.sql()?;
-        let mut stmt = self.conn.prepare(sql)?;
-        let marker = format!(":{}", PRIMARY_KEY);
-        stmt.bind_by_name(&marker, key)?;
-        for (key, value) in values {
-            let marker = format!(":{}", key);
-            stmt.bind_by_name(&marker, &value[..])?;
+        Ok(())
+    }
+
+    fn print_pragmas(&self) -> Result<()> {
+        for name in PRAGMAS {
+            let mut value = String::new();
+            self.conn.iterate(format!("PRAGMA {}", name), |row| {
+                if let Some((_, Some(v))) = row.first() {
+                    value = v.to_string();
+                }
+                true
+            })?;
+            println!("[SQLITE], {}, {}", name, value);
         }
-        let state = stmt.next()?;
-        assert!(state == State::Done);
         Ok(())
     }
 
-    fn read(&self, table: &str, key: &str, result: &mut HashMap<String, String>) -> Result<()> {
-        // TODO: cache prepared statement
-        let mut sql = SqlBuilder::select_from(table);
-        sql.field("*");
-        // TODO: fields
-        sql.and_where(format!("{} = :{}", PRIMARY_KEY, PRIMARY_KEY));
-        let sql = sql.sql()?;
-        let mut stmt = self.conn.prepare(sql)?;
-        let marker = format!(":{}", PRIMARY_KEY);
-        stmt.bind_by_name(&marker, key)?;
-        while let State::Row = stmt.next().unwrap() {
-            for idx in 0..stmt.column_count() {
-                let key = stmt.column_name(idx);
-                let value = stmt.read::<String>(idx).unwrap();
-                result.insert(key.to_string(), value);
+    // SQLite rolls back the whole transaction on some errors, e.g. SQLITE_FULL,
+    // SQLITE_IOERR or SQLITE_NOMEM, and returns to autocommit mode.
+    fn in_transaction(&self) -> bool {
+        unsafe { sqlite3_sys::sqlite3_get_autocommit(self.conn.as_raw()) == 0 }
+    }
+
+    fn commit(&self) -> Result<()> {
+        if self.pending_inserts.get() > 0 {
+            let result = self.conn.execute("COMMIT");
+            // A failed COMMIT may also have rolled the transaction back.
+            if result.is_ok() || !self.in_transaction() {
+                self.pending_inserts.set(0);
             }
+            result?;
         }
-        // TODO: results
         Ok(())
     }
+
+    fn create_table(&self) -> Result<()> {
+        let mut columns = vec![format!("{} TEXT PRIMARY KEY", PRIMARY_KEY)];
+        for field in self.props.field_names() {
+            columns.push(format!("{} BLOB", field));
+        }
+        let sql = format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            self.props.table,
+            columns.join(", ")
+        );
+        self.conn.execute(sql)?;
+        Ok(())
+    }
+
+    fn insert_row(
+        &self,
+        table: &str,
+        key: &str,
+        values: &HashMap<&str, Vec<u8>>,
+        batched: bool,
+    ) -> Result<Status> {
+        // Other threads may read a record as soon as a run phase insert
+        // returns, so only load phase inserts are batched.
+        let batch_size = if batched { self.props.batch_size } else { 1 };
+        if batch_size == 1 {
+            self.commit()?;
+        }
+        if batch_size > 1 && self.pending_inserts.get() == 0 {
+            self.conn.execute("BEGIN IMMEDIATE")?;
+        }
+        let mut fields: Vec<&str> = values.keys().copied().collect();
+        fields.sort_unstable();
+        let sql = || {
+            let mut sql = SqlBuilder::insert_into(table);
+            let mut vals: Vec<String> = Vec::new();
+            sql.field(PRIMARY_KEY);
+            vals.push(format!(":{}", PRIMARY_KEY));
+            for field in &fields {
+                sql.field(field);
+                vals.push(format!(":{}", field));
+            }
+            sql.values(&vals);
+            sql.sql()
+        };
+        let result = self.with_statement(Operation::Insert, table, &fields, sql, |stmt| {
+            stmt.bind(1, key)?;
+            for (idx, field) in fields.iter().enumerate() {
+                stmt.bind(idx + 2, &values[field][..])?;
+            }
+            match stmt.next()? {
+                State::Done => Ok(Status::Ok),
+                State::Row => Ok(Status::UnexpectedState),
+            }
+        });
+        if batch_size > 1 && result.is_err() && !self.in_transaction() {
+            // The inserts of the batch were rolled back with it.
+            self.pending_inserts.set(0);
+            return result;
+        }
+        // Otherwise a failed insert, e.g. a constraint violation, does not
+        // abort the transaction, so it still counts towards the batch.
+        if batch_size > 1 {
+            self.pending_inserts.set(self.pending_inserts.get() + 1);
+            if self.pending_inserts.get() >= batch_size {
+                self.commit()?;
+            } else if let Ok(Status::Ok) = result {
+                return Ok(Status::BatchedOk);
+            }
+        }
+        result
+    }
+
+    fn read_row(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut HashMap<String, Vec<u8>>,
+    ) -> Result<Status> {
+        // Reading all fields is cached like an empty selection.
+        let fields = fields.unwrap_or(&[]);
+        let sql = || {
+            let mut sql = SqlBuilder::select_from(table);
+            match fields {
+                [] => sql.fields(&self.props.field_names()),
+                fields => sql.fields(fields),
+            };
+            sql.and_where(format!("{} = :{}", PRIMARY_KEY, PRIMARY_KEY));
+            sql.sql()
+        };
+        self.with_statement(Operation::Read, table, fields, sql, |stmt| {
+            stmt.bind(1, key)?;
+            let mut found = false;
+            while let State::Row = stmt.next()? {
+                found = true;
+                // Fields that were never written are NULL and skipped.
+                for idx in 0..stmt.column_count() {
+                    if let Some(value) = stmt.read::<Option<Vec<u8>>>(idx)? {
+                        result.insert(stmt.column_name(idx).to_string(), value);
+                    }
+                }
+            }
+            if !found {
+                return Ok(Status::NotFound);
+            }
+            Ok(Status::Ok)
+        })
+    }
+}
+
+impl DB for SQLite {
+    fn init(&self) -> Result<()> {
+        if self.props.sqlite.drop_table {
+            let mut result = Ok(());
+            DROP_TABLE.call_once(|| {
+                result = self
+                    .conn
+                    .execute(format!("DROP TABLE IF EXISTS {}", self.props.table));
+            });
+            result?;
+        }
+        self.apply_pragmas()?;
+        let mut result = Ok(());
+        PRINT_PRAGMAS.call_once(|| {
+            result = self.print_pragmas();
+        });
+        result?;
+        self.create_table()
+    }
+
+    fn cleanup(&self) -> Result<()> {
+        self.commit()
+    }
+
+    fn insert(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status_with(self.insert_row(table, key, values, false), classify)
+    }
+
+    fn insert_batched(&self, table: &str, key: &str, values: &HashMap<&str, Vec<u8>>) -> Status {
+        error_status_with(self.insert_row(table, key, values, true), classify)
+    }
+
+    fn read(
+        &self,
+        table: &str,
+        key: &str,
+        fields: Option<&[&str]>,
+        result: &mut HashMap<String, Vec<u8>>,
+    ) -> Status {
+        error_status_with(self.read_row(table, key, fields, result), classify)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_db() -> SQLite {
+        let props: Properties =
+            toml::from_str("operationcount = 1\nfieldcount = 1\n[sqlite]\npath = \":memory:\"")
+                .unwrap();
+        let db = SQLite::new(Arc::new(props)).unwrap();
+        db.init().unwrap();
+        db
+    }
+
+    #[test]
+    fn test_duplicate_insert_is_constraint() {
+        let db = memory_db();
+        let mut values = HashMap::new();
+        values.insert("field0", b"value".to_vec());
+        assert_eq!(db.insert("usertable", "key", &values), Status::Ok);
+        assert_eq!(db.insert("usertable", "key", &values), Status::Constraint);
+    }
+
+    #[test]
+    fn test_read_missing_key_is_not_found() {
+        let db = memory_db();
+        let mut values = HashMap::new();
+        values.insert("field0", b"value".to_vec());
+        assert_eq!(db.insert("usertable", "key", &values), Status::Ok);
+        let mut result = HashMap::new();
+        assert_eq!(db.read("usertable", "key", None, &mut result), Status::Ok);
+        assert_eq!(result["field0"], b"value");
+        let mut result = HashMap::new();
+        assert_eq!(
+            db.read("usertable", "missing", None, &mut result),
+            Status::NotFound
+        );
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_statements_are_cached_per_table() {
+        let db = memory_db();
+        db.conn
+            .execute("CREATE TABLE othertable (y_id TEXT PRIMARY KEY, field0 BLOB)")
+            .unwrap();
+        let mut values = HashMap::new();
+        values.insert("field0", b"value".to_vec());
+        assert_eq!(db.insert("usertable", "key", &values), Status::Ok);
+        assert_eq!(db.insert("othertable", "key", &values), Status::Ok);
+        let mut result = HashMap::new();
+        assert_eq!(db.read("othertable", "key", None, &mut result), Status::Ok);
+        assert_eq!(db.statements.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_rolled_back_batch_starts_a_new_transaction() {
+        let props: Properties = toml::from_str(
+            "operationcount = 1\nfieldcount = 1\nbatchsize = 1000\n[sqlite]\npath = \":memory:\"",
+        )
+        .unwrap();
+        let db = SQLite::new(Arc::new(props)).unwrap();
+        db.init().unwrap();
+        db.conn.execute("PRAGMA max_page_count = 16").unwrap();
+        let mut values = HashMap::new();
+        values.insert("field0", vec![0; 4096]);
+        let mut key = 0;
+        let status = loop {
+            key += 1;
+            match db.insert_batched("usertable", &key.to_string(), &values) {
+                Status::BatchedOk => continue,
+                status => break status,
+            }
+        };
+        assert_eq!(status, Status::Error);
+        assert!(!db.in_transaction());
+        assert_eq!(db.pending_inserts.get(), 0);
+        db.conn.execute("PRAGMA max_page_count = 1000").unwrap();
+        assert_eq!(
+            db.insert_batched("usertable", "a", &values),
+            Status::BatchedOk
+        );
+        db.cleanup().unwrap();
+        let mut result = HashMap::new();
+        assert_eq!(db.read("usertable", "a", None, &mut result), Status::Ok);
+        assert_eq!(
+            db.read("usertable", "1", None, &mut result),
+            Status::NotFound
+        );
+    }
+
+    #[test]
+    fn test_failed_commit_starts_a_new_batch() {
+        let props: Properties = toml::from_str(
+            "operationcount = 1\nfieldcount = 1\nbatchsize = 1000\n[sqlite]\npath = \":memory:\"",
+        )
+        .unwrap();
+        let db = SQLite::new(Arc::new(props)).unwrap();
+        db.init().unwrap();
+        let mut values = HashMap::new();
+        values.insert("field0", b"value".to_vec());
+        assert_eq!(
+            db.insert_batched("usertable", "a", &values),
+            Status::BatchedOk
+        );
+        // Stands in for SQLite rolling back the transaction on e.g. SQLITE_FULL.
+        db.conn.execute("ROLLBACK").unwrap();
+        assert!(db.cleanup().is_err());
+        assert_eq!(db.pending_inserts.get(), 0);
+        assert_eq!(
+            db.insert_batched("usertable", "b", &values),
+            Status::BatchedOk
+        );
+        db.cleanup().unwrap();
+        let mut result = HashMap::new();
+        assert_eq!(db.read("usertable", "b", None, &mut result), Status::Ok);
+    }
+
+    #[test]
+    fn test_run_phase_inserts_are_not_batched() {
+        let props: Properties = toml::from_str(
+            "operationcount = 1\nfieldcount = 1\nbatchsize = 1000\n[sqlite]\npath = \":memory:\"",
+        )
+        .unwrap();
+        let db = SQLite::new(Arc::new(props)).unwrap();
+        db.init().unwrap();
+        let mut values = HashMap::new();
+        values.insert("field0", b"value".to_vec());
+        assert_eq!(
+            db.insert_batched("usertable", "a", &values),
+            Status::BatchedOk
+        );
+        assert_eq!(db.insert("usertable", "b", &values), Status::Ok);
+        assert!(!db.in_transaction());
+        assert_eq!(db.pending_inserts.get(), 0);
+    }
+
+    #[test]
+    fn test_read_fields() {
+        let props: Properties =
+            toml::from_str("operationcount = 1\nfieldcount = 2\n[sqlite]\npath = \":memory:\"")
+                .unwrap();
+        let db = SQLite::new(Arc::new(props)).unwrap();
+        db.init().unwrap();
+        let mut values = HashMap::new();
+        values.insert("field0", b"0".to_vec());
+        values.insert("field1", b"1".to_vec());
+        assert_eq!(db.insert("usertable", "key", &values), Status::Ok);
+        let mut result = HashMap::new();
+        let fields = Some(&["field1"][..]);
+        assert_eq!(db.read("usertable", "key", fields, &mut result), Status::Ok);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result["field1"], b"1");
+        let mut result = HashMap::new();
+        assert_eq!(db.read("usertable", "key", None, &mut result), Status::Ok);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result["field0"], b"0");
+        assert_eq!(result["field1"], b"1");
+    }
 }
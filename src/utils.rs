@@ -0,0 +1,28 @@
+// http://en.wikipedia.org/wiki/Fowler_Noll_Vo_hash
+pub fn fnvhash64(val: u64) -> u64 {
+    fnvhash64_bytes(&val.to_le_bytes())
+}
+
+// Hashes the bytes in order, so the result is stable across runs and
+// platforms, unlike `DefaultHasher`.
+pub fn fnvhash64_bytes(bytes: &[u8]) -> u64 {
+    let prime = 0xcbf29ce484222325;
+    let mut hashval = prime;
+    for octet in bytes {
+        hashval ^= *octet as u64;
+        hashval = hashval.wrapping_mul(prime);
+    }
+    hashval
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnvhash64() {
+        // Keys of existing databases depend on these values.
+        assert_eq!(fnvhash64(0), 0x4b2a24074bf69585);
+        assert_eq!(fnvhash64(1), fnvhash64_bytes(&[1, 0, 0, 0, 0, 0, 0, 0]));
+    }
+}
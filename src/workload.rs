@@ -1,11 +1,11 @@
 mod core_workload;
 
-pub use core_workload::CoreWorkload;
+pub use core_workload::{CoreOperation, CoreWorkload};
 
 use crate::db::DB;
-use std::rc::Rc;
+use rand::rngs::SmallRng;
 
 pub trait Workload {
-    fn do_insert(&self, db: Rc<dyn DB>);
-    fn do_transaction(&self, db: Rc<dyn DB>);
+    fn do_insert(&self, db: &dyn DB, rng: &mut SmallRng);
+    fn do_transaction(&self, db: &dyn DB, rng: &mut SmallRng);
 }
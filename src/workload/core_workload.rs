@@ -1,17 +1,18 @@
-use crate::db::DB;
+use crate::db::{Status, DB};
+use crate::measurements::Measurements;
 use crate::workload::Workload;
-use rand::distributions::{Alphanumeric, DistString};
 use rand::rngs::SmallRng;
 use rand::SeedableRng;
 use std::collections::HashMap;
-use std::rc::Rc;
-use std::sync::Mutex;
+use std::sync::Arc;
 
 use crate::generator::{
     AcknowledgedCounterGenerator, ConstantGenerator, CounterGenerator, DiscreteGenerator,
-    Generator, UniformLongGenerator, WeightPair, ZipfianGenerator,
+    Generator, HistogramGenerator, SequentialGenerator, SkewedLatestGenerator,
+    UniformLongGenerator, ValueGenerator, WeightPair, ZipfianGenerator,
 };
 use crate::properties::Properties;
+use crate::utils::fnvhash64;
 
 #[derive(Copy, Clone, Debug)]
 pub enum CoreOperation {
@@ -22,6 +23,26 @@ pub enum CoreOperation {
     ReadModifyWrite,
 }
 
+impl CoreOperation {
+    pub const ALL: [CoreOperation; 5] = [
+        CoreOperation::Read,
+        CoreOperation::Update,
+        CoreOperation::Insert,
+        CoreOperation::Scan,
+        CoreOperation::ReadModifyWrite,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CoreOperation::Read => "READ",
+            CoreOperation::Update => "UPDATE",
+            CoreOperation::Insert => "INSERT",
+            CoreOperation::Scan => "SCAN",
+            CoreOperation::ReadModifyWrite => "READ-MODIFY-WRITE",
+        }
+    }
+}
+
 impl std::fmt::Display for CoreOperation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self)
@@ -30,134 +51,221 @@ impl std::fmt::Display for CoreOperation {
 
 #[allow(dead_code)]
 pub struct CoreWorkload {
-    rng: Mutex<SmallRng>,
+    seed: Option<u64>,
     table: String,
     field_count: u64,
     field_names: Vec<String>,
-    field_length_generator: Mutex<Box<dyn Generator<u64> + Send>>,
+    field_length_generator: Box<dyn Generator<u64> + Send + Sync>,
+    value_generator: ValueGenerator,
     read_all_fields: bool,
     write_all_fields: bool,
     data_integrity: bool,
-    key_sequence: Mutex<Box<dyn Generator<u64> + Send>>,
-    operation_chooser: Mutex<DiscreteGenerator<CoreOperation>>,
-    key_chooser: Mutex<Box<dyn Generator<u64> + Send>>,
-    //field_chooser: Box<dyn Generator<String>>,
-    transaction_insert_key_sequence: Mutex<AcknowledgedCounterGenerator>,
-    //scan_length: Box<dyn Generator<u64>>,
+    key_sequence: Box<dyn Generator<u64> + Send + Sync>,
+    operation_chooser: DiscreteGenerator<CoreOperation>,
+    key_chooser: Box<dyn Generator<u64> + Send + Sync>,
+    field_chooser: UniformLongGenerator,
+    transaction_insert_key_sequence: Arc<AcknowledgedCounterGenerator>,
+    scan_length: Box<dyn Generator<u64> + Send + Sync>,
     ordered_inserts: bool,
     record_count: usize,
     zero_padding: usize,
     insertion_retry_limit: u64,
     insertion_retry_interval: u64,
+    measurements: Measurements,
 }
 
 impl CoreWorkload {
     pub fn new(prop: &Properties) -> Self {
-        let rng = SmallRng::from_entropy();
-        let field_name_prefix = "field";
-        let field_count = 10;
-        let mut field_names = vec![];
-        for i in 0..field_count {
-            field_names.push(format!("{}{}", field_name_prefix, i));
-        }
+        // Inserts during the run phase continue after the loaded records.
+        let transaction_insert_key_sequence =
+            Arc::new(AcknowledgedCounterGenerator::new(prop.record_count));
         CoreWorkload {
-            rng: Mutex::new(rng),
-            table: String::from("usertable"),
-            field_count,
-            field_names,
-            field_length_generator: Mutex::new(get_field_length_generator(prop)),
-            read_all_fields: true,
-            write_all_fields: true,
+            seed: prop.seed,
+            table: prop.table.clone(),
+            field_count: prop.field_count,
+            field_names: prop.field_names(),
+            field_length_generator: get_field_length_generator(prop),
+            value_generator: get_value_generator(prop),
+            read_all_fields: prop.read_all_fields,
+            write_all_fields: prop.write_all_fields,
             data_integrity: true,
-            key_sequence: Mutex::new(Box::new(CounterGenerator::new(prop.insert_start))),
-            operation_chooser: Mutex::new(create_operation_generator(prop)),
-            key_chooser: Mutex::new(get_key_chooser_generator(prop)),
-            //field_chooser: Box<dyn Generator<String>>,
-            transaction_insert_key_sequence: Mutex::new(AcknowledgedCounterGenerator::new(1)),
-            //scan_length: Box<dyn Generator<u64>>,
+            key_sequence: Box::new(CounterGenerator::new(prop.insert_start)),
+            operation_chooser: create_operation_generator(prop),
+            key_chooser: get_key_chooser_generator(prop, &transaction_insert_key_sequence),
+            field_chooser: UniformLongGenerator::new(0, prop.field_count - 1),
+            transaction_insert_key_sequence,
+            scan_length: get_scan_length_generator(prop),
             ordered_inserts: true,
             record_count: 1,
             zero_padding: 1,
             insertion_retry_limit: 0,
             insertion_retry_interval: 0,
+            measurements: Measurements::new(),
+        }
+    }
+
+    /// Returns the random number generator for worker thread `thread_id`.
+    ///
+    /// With a configured seed, every thread gets its own deterministic stream
+    /// so that runs can be reproduced.
+    pub fn thread_rng(&self, thread_id: usize) -> SmallRng {
+        match self.seed {
+            // Hash the seed, so that neighbouring seeds don't share threads'
+            // streams, e.g. thread 1 of seed 1 with thread 0 of seed 2.
+            Some(seed) => SmallRng::seed_from_u64(fnvhash64(seed) ^ thread_id as u64),
+            None => SmallRng::from_entropy(),
         }
     }
 
-    fn do_transaction_read(&self, db: Rc<dyn DB>) {
-        let keynum = self.next_key_num();
-        let dbkey = format!("{}", fnvhash64(keynum));
+    pub fn measurements(&self) -> &Measurements {
+        &self.measurements
+    }
+
+    fn do_transaction_read(&self, db: &dyn DB, rng: &mut SmallRng) {
+        let keynum = self.next_key_num(rng);
+        let dbkey = build_key_name(keynum);
+        let field = self.choose_read_field(rng);
         let mut result = HashMap::new();
-        db.read(&self.table, &dbkey, &mut result).unwrap();
+        let status = db.read(
+            &self.table,
+            &dbkey,
+            field.as_ref().map(std::slice::from_ref),
+            &mut result,
+        );
+        self.measurements.record(CoreOperation::Read, status);
         // TODO: verify rows
     }
 
-    fn next_key_num(&self) -> u64 {
+    fn do_transaction_update(&self, db: &dyn DB, rng: &mut SmallRng) {
+        let keynum = self.next_key_num(rng);
+        let dbkey = build_key_name(keynum);
+        let values = self.build_update_values(rng);
+        let status = db.update(&self.table, &dbkey, &values);
+        self.measurements.record(CoreOperation::Update, status);
+    }
+
+    fn do_transaction_insert(&self, db: &dyn DB, rng: &mut SmallRng) {
+        let keynum = self.transaction_insert_key_sequence.next_value(rng);
+        let dbkey = build_key_name(keynum);
+        let values = self.build_values(rng);
+        let status = db.insert(&self.table, &dbkey, &values);
+        // Acknowledge even failed inserts, otherwise reads never move past
+        // this key.
+        self.transaction_insert_key_sequence.acknowledge(keynum);
+        self.measurements.record(CoreOperation::Insert, status);
+    }
+
+    fn do_transaction_scan(&self, db: &dyn DB, rng: &mut SmallRng) {
+        let keynum = self.next_key_num(rng);
+        let start_key = build_key_name(keynum);
+        let len = self.scan_length.next_value(rng) as usize;
+        let mut result = Vec::new();
+        let status = db.scan(&self.table, &start_key, len, &mut result);
+        self.measurements.record(CoreOperation::Scan, status);
+    }
+
+    fn do_transaction_read_modify_write(&self, db: &dyn DB, rng: &mut SmallRng) {
+        let keynum = self.next_key_num(rng);
+        let dbkey = build_key_name(keynum);
+        let field = self.choose_read_field(rng);
+        let values = self.build_update_values(rng);
+        let mut result = HashMap::new();
+        let mut status = db.read(
+            &self.table,
+            &dbkey,
+            field.as_ref().map(std::slice::from_ref),
+            &mut result,
+        );
+        if status == Status::Ok {
+            status = db.update(&self.table, &dbkey, &values);
+        }
+        self.measurements
+            .record(CoreOperation::ReadModifyWrite, status);
+    }
+
+    fn next_key_num(&self, rng: &mut SmallRng) -> u64 {
         // FIXME: Handle case where keychooser is an ExponentialGenerator.
-        // FIXME: Handle case where keynum is > transactioninsertkeysequence's last value
-        self.key_chooser
-            .lock()
-            .unwrap()
-            .next_value(&mut self.rng.lock().unwrap())
+        // Skip keys whose insert has not been acknowledged yet.
+        loop {
+            let keynum = self.key_chooser.next_value(rng);
+            if keynum <= self.transaction_insert_key_sequence.last_value() {
+                return keynum;
+            }
+        }
     }
-}
 
-impl Workload for CoreWorkload {
-    fn do_insert(&self, db: Rc<dyn DB>) {
-        let dbkey = self
-            .key_sequence
-            .lock()
-            .unwrap()
-            .next_value(&mut self.rng.lock().unwrap());
-        let dbkey = format!("{}", fnvhash64(dbkey));
+    fn build_values(&self, rng: &mut SmallRng) -> HashMap<&str, Vec<u8>> {
         let mut values = HashMap::new();
         for field_name in &self.field_names {
-            let field_len = self
-                .field_length_generator
-                .lock()
-                .unwrap()
-                .next_value(&mut self.rng.lock().unwrap());
-            let s = Alphanumeric
-                .sample_string::<SmallRng>(&mut self.rng.lock().unwrap(), field_len as usize);
-            values.insert(&field_name[..], s);
-        }
-        db.insert(&self.table, &dbkey, &values).unwrap();
-    }
-
-    fn do_transaction(&self, db: Rc<dyn DB>) {
-        let op = self
-            .operation_chooser
-            .lock()
-            .unwrap()
-            .next_value(&mut self.rng.lock().unwrap());
+            values.insert(&field_name[..], self.build_value(rng));
+        }
+        values
+    }
+
+    // `None` reads every field, otherwise a single random one is read, see
+    // `readallfields`.
+    fn choose_read_field(&self, rng: &mut SmallRng) -> Option<&str> {
+        if self.read_all_fields {
+            return None;
+        }
+        Some(&self.field_names[self.field_chooser.next_value(rng) as usize])
+    }
+
+    // Either every field or a single random one, see `writeallfields`.
+    fn build_update_values(&self, rng: &mut SmallRng) -> HashMap<&str, Vec<u8>> {
+        if self.write_all_fields {
+            return self.build_values(rng);
+        }
+        let field = &self.field_names[self.field_chooser.next_value(rng) as usize];
+        let mut values = HashMap::new();
+        values.insert(&field[..], self.build_value(rng));
+        values
+    }
+
+    fn build_value(&self, rng: &mut SmallRng) -> Vec<u8> {
+        let field_len = self.field_length_generator.next_value(rng);
+        self.value_generator.next_value(field_len as usize, rng)
+    }
+}
+
+impl Workload for CoreWorkload {
+    fn do_insert(&self, db: &dyn DB, rng: &mut SmallRng) {
+        let keynum = self.key_sequence.next_value(rng);
+        let dbkey = build_key_name(keynum);
+        let values = self.build_values(rng);
+        let status = db.insert_batched(&self.table, &dbkey, &values);
+        self.measurements.record(CoreOperation::Insert, status);
+    }
+
+    fn do_transaction(&self, db: &dyn DB, rng: &mut SmallRng) {
+        let op = self.operation_chooser.next_value(rng);
         match op {
-            CoreOperation::Read => {
-                self.do_transaction_read(db);
-            }
-            _ => todo!(),
+            CoreOperation::Read => self.do_transaction_read(db, rng),
+            CoreOperation::Update => self.do_transaction_update(db, rng),
+            CoreOperation::Insert => self.do_transaction_insert(db, rng),
+            CoreOperation::Scan => self.do_transaction_scan(db, rng),
+            CoreOperation::ReadModifyWrite => self.do_transaction_read_modify_write(db, rng),
         }
     }
 }
 
-// http://en.wikipedia.org/wiki/Fowler_Noll_Vo_hash
-fn fnvhash64(val: u64) -> u64 {
-    let mut val = val;
-    let prime = 0xcbf29ce484222325;
-    let mut hashval = prime;
-    for _ in 0..8 {
-        let octet = val & 0x00ff;
-        val >>= 8;
-        hashval ^= octet;
-        hashval = hashval.wrapping_mul(prime);
-    }
-    hashval
+fn build_key_name(keynum: u64) -> String {
+    format!("{}", fnvhash64(keynum))
 }
 
-fn get_field_length_generator(prop: &Properties) -> Box<dyn Generator<u64> + Send> {
+fn get_field_length_generator(prop: &Properties) -> Box<dyn Generator<u64> + Send + Sync> {
     match prop.field_length_distribution.to_lowercase().as_str() {
         "constant" => Box::new(ConstantGenerator::new(prop.field_length)),
         "uniform" => Box::new(UniformLongGenerator::new(1, prop.field_length)),
         "zipfian" => Box::new(ZipfianGenerator::from_range(1, prop.field_length)),
-        "histogram" => unimplemented!(),
+        "histogram" => Box::new(
+            HistogramGenerator::from_file(&prop.field_length_histogram_file).unwrap_or_else(|e| {
+                panic!(
+                    "failed to load field length histogram {}: {}",
+                    prop.field_length_histogram_file, e
+                )
+            }),
+        ),
         _ => panic!(
             "unknown field length distribution {}",
             prop.field_length_distribution
@@ -165,7 +273,30 @@ fn get_field_length_generator(prop: &Properties) -> Box<dyn Generator<u64> + Sen
     }
 }
 
-fn get_key_chooser_generator(prop: &Properties) -> Box<dyn Generator<u64> + Send> {
+fn get_value_generator(prop: &Properties) -> ValueGenerator {
+    match prop.value_generator.to_lowercase().as_str() {
+        "alphanumeric" => ValueGenerator::Alphanumeric,
+        "random" => ValueGenerator::Random,
+        "constant" => ValueGenerator::Constant(prop.value_constant_byte),
+        "pattern" => {
+            assert!(!prop.value_pattern.is_empty(), "valuepattern is empty");
+            ValueGenerator::Pattern(prop.value_pattern.as_bytes().to_vec())
+        }
+        "compressible" => {
+            assert!(
+                prop.value_compression_ratio >= 1.0,
+                "valuecompressionratio must be at least 1.0"
+            );
+            ValueGenerator::Compressible(prop.value_compression_ratio)
+        }
+        _ => panic!("unknown value generator {}", prop.value_generator),
+    }
+}
+
+fn get_key_chooser_generator(
+    prop: &Properties,
+    transaction_insert_key_sequence: &Arc<AcknowledgedCounterGenerator>,
+) -> Box<dyn Generator<u64> + Send + Sync> {
     let insert_count = if prop.insert_count > 1 {
         prop.insert_count
     } else {
@@ -177,10 +308,30 @@ fn get_key_chooser_generator(prop: &Properties) -> Box<dyn Generator<u64> + Send
             prop.insert_start,
             prop.insert_start + insert_count - 1,
         )),
+        "sequential" => Box::new(SequentialGenerator::new(
+            prop.insert_start,
+            prop.insert_start + insert_count - 1,
+        )),
+        "latest" => Box::new(SkewedLatestGenerator::new(
+            transaction_insert_key_sequence.clone(),
+        )),
         _ => todo!(),
     }
 }
 
+fn get_scan_length_generator(prop: &Properties) -> Box<dyn Generator<u64> + Send + Sync> {
+    let (min, max) = (prop.min_scan_length, prop.max_scan_length);
+    assert!(min <= max, "minscanlength is larger than maxscanlength");
+    match prop.scan_length_distribution.to_lowercase().as_str() {
+        "uniform" => Box::new(UniformLongGenerator::new(min, max)),
+        "zipfian" => Box::new(ZipfianGenerator::from_range(min, max)),
+        _ => panic!(
+            "unknown scan length distribution {}",
+            prop.scan_length_distribution
+        ),
+    }
+}
+
 fn create_operation_generator(prop: &Properties) -> DiscreteGenerator<CoreOperation> {
     let mut pairs = vec![];
     if prop.read_proportion > 0.0 {
@@ -210,3 +361,86 @@ fn create_operation_generator(prop: &Properties) -> DiscreteGenerator<CoreOperat
 
     DiscreteGenerator::new(pairs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryDB;
+    use rand::Rng;
+
+    #[test]
+    fn test_seeded_thread_rng() {
+        let props: Properties =
+            toml::from_str("operationcount = 10\nrecordcount = 10\nseed = 42").unwrap();
+        let wl = CoreWorkload::new(&props);
+        let a: u64 = wl.thread_rng(0).gen();
+        let b: u64 = wl.thread_rng(0).gen();
+        let c: u64 = wl.thread_rng(1).gen();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        let props: Properties =
+            toml::from_str("operationcount = 10\nrecordcount = 10\nseed = 43").unwrap();
+        let d: u64 = CoreWorkload::new(&props).thread_rng(0).gen();
+        assert_ne!(c, d);
+    }
+
+    #[test]
+    fn test_load_and_run_against_memory_db() {
+        let props: Properties = toml::from_str(
+            "operationcount = 100\nrecordcount = 100\nreadproportion = 1.0\n\
+             updateproportion = 0.0\ntable = \"core_workload_test\"",
+        )
+        .unwrap();
+        let wl = CoreWorkload::new(&props);
+        let db = MemoryDB::new().unwrap();
+        let mut rng = wl.thread_rng(0);
+        for _ in 0..props.record_count {
+            wl.do_insert(&db, &mut rng);
+        }
+        for _ in 0..props.operation_count {
+            wl.do_transaction(&db, &mut rng);
+        }
+        let measurements = wl.measurements();
+        assert_eq!(measurements.count(CoreOperation::Insert, Status::Ok), 100);
+        assert_eq!(measurements.count(CoreOperation::Read, Status::Ok), 100);
+    }
+
+    #[test]
+    fn test_all_operations_against_memory_db() {
+        let props: Properties = toml::from_str(
+            "operationcount = 1000\nrecordcount = 100\nreadproportion = 0.2\n\
+             updateproportion = 0.2\ninsertproportion = 0.2\nscanproportion = 0.2\n\
+             readmodifywriteproportion = 0.2\nmaxscanlength = 10\nseed = 1\n\
+             readallfields = false\nwriteallfields = false\n\
+             table = \"core_workload_all_operations\"",
+        )
+        .unwrap();
+        let wl = CoreWorkload::new(&props);
+        let db = MemoryDB::new().unwrap();
+        let mut rng = wl.thread_rng(0);
+        for _ in 0..props.record_count {
+            wl.do_insert(&db, &mut rng);
+        }
+        for _ in 0..props.operation_count {
+            wl.do_transaction(&db, &mut rng);
+        }
+        let measurements = wl.measurements();
+        let mut total = 0;
+        for op in CoreOperation::ALL {
+            let count = measurements.count(op, Status::Ok);
+            assert!(count > 0, "no successful {} operations", op.name());
+            total += count;
+        }
+        assert_eq!(total, props.record_count + props.operation_count);
+        // Run phase inserts continue after the loaded keys.
+        let inserts = measurements.count(CoreOperation::Insert, Status::Ok) - props.record_count;
+        assert_eq!(
+            wl.transaction_insert_key_sequence.last_value(),
+            props.record_count + inserts - 1
+        );
+        let mut result = Vec::new();
+        let scanned = db.scan(&wl.table, "", usize::MAX, &mut result);
+        assert_eq!(scanned, Status::Ok);
+        assert_eq!(result.len() as u64, props.record_count + inserts);
+    }
+}